@@ -0,0 +1,261 @@
+//! Apgcodes, the names Catagolue gives objects such as `xs4_33` for the
+//! block, `xp2_7` for the blinker and `xq4_153` for the glider.
+//!
+//! The prefix gives the kind of object: `xs` and its population for still
+//! lifes, `xp` and its period for oscillators, `xq` and its period for
+//! spaceships. After the underscore the cells are written in extended
+//! Wechsler format, in strips five rows tall separated by `z`. Each column
+//! of a strip is a character from `0` to `v` with the top row in its lowest
+//! bit, and runs of empty columns are shortened to `w` for two, `x` for
+//! three and `y` followed by a count for four or more.
+
+use std::collections::HashSet;
+use std::fmt;
+
+use wasm_bindgen::prelude::*;
+
+use crate::rule::RuleError;
+use crate::sparse::SparseUniverse;
+
+/// The characters for a column of a strip, or the length of a run of empty
+/// columns after a `y`.
+const DIGITS: &[u8; 36] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+/// How many generations an object is run for while looking for its period.
+const MAX_PERIOD: u64 = 1024;
+
+/// Where a rotation or reflection moves the cell at a row and column.
+type Orientation = fn(i64, i64) -> (i64, i64);
+
+/// The eight ways an object can be rotated and reflected.
+const ORIENTATIONS: [Orientation; 8] = [
+    |row, col| (row, col),
+    |row, col| (row, -col),
+    |row, col| (-row, col),
+    |row, col| (-row, -col),
+    |row, col| (col, row),
+    |row, col| (col, -row),
+    |row, col| (-col, row),
+    |row, col| (-col, -row),
+];
+
+/// Give the apgcode of an object under a rule.
+///
+/// The object is run on an unbounded plane until it returns to its starting
+/// shape, and is named by whichever of its phases and orientations has the
+/// shortest code, taking the first in alphabetical order between codes of
+/// the same length.
+pub fn encode_apgcode(cells: &[(i64, i64)], rule: &str) -> Result<String, ApgcodeError> {
+    let start = normalize(cells);
+    if start.is_empty() {
+        return Err(ApgcodeError::Empty);
+    }
+
+    let mut universe = SparseUniverse::new();
+    universe.set_rule(rule).map_err(ApgcodeError::Rule)?;
+    universe.set_cells(cells);
+
+    let origin = top_left(cells);
+    let mut phases = vec![start.clone()];
+    for period in 1..=MAX_PERIOD {
+        universe.tick();
+        let cells = universe.live_cells();
+        let phase = normalize(&cells);
+
+        if phase == start {
+            let prefix = if top_left(&cells) != origin {
+                format!("xq{}", period)
+            } else if period == 1 {
+                format!("xs{}", start.len())
+            } else {
+                format!("xp{}", period)
+            };
+            let body = phases
+                .iter()
+                .flat_map(|phase| {
+                    ORIENTATIONS
+                        .iter()
+                        .map(move |orient| wechsler(phase, *orient))
+                })
+                .min_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)))
+                .unwrap_or_default();
+            return Ok(format!("{}_{}", prefix, body));
+        }
+        phases.push(phase);
+    }
+
+    Err(ApgcodeError::NotPeriodic)
+}
+
+/// The cells of an apgcode as rows and columns from its top left corner.
+pub fn decode_apgcode(code: &str) -> Result<Vec<(i64, i64)>, ApgcodeError> {
+    let (prefix, body) = code
+        .split_once('_')
+        .ok_or_else(|| ApgcodeError::invalid(code.len() + 1, "expected a '_'"))?;
+    let valid_prefix = ["xs", "xp", "xq"].iter().any(|kind| {
+        prefix
+            .strip_prefix(kind)
+            .is_some_and(|count| !count.is_empty() && count.bytes().all(|b| b.is_ascii_digit()))
+    });
+    if !valid_prefix {
+        return Err(ApgcodeError::invalid(
+            1,
+            format!("unsupported prefix '{}'", prefix),
+        ));
+    }
+
+    let mut cells = Vec::new();
+    let (mut strip, mut col) = (0i64, 0i64);
+    let mut chars = body
+        .bytes()
+        .enumerate()
+        .map(|(i, c)| (prefix.len() + 2 + i, c));
+    while let Some((column, c)) = chars.next() {
+        match c {
+            b'0'..=b'9' | b'a'..=b'v' => {
+                let bits = DIGITS.iter().position(|&d| d == c).unwrap_or(0);
+                for row in 0..5 {
+                    if bits & (1 << row) != 0 {
+                        cells.push((strip * 5 + row, col));
+                    }
+                }
+                col += 1;
+            }
+            b'w' => col += 2,
+            b'x' => col += 3,
+            b'y' => {
+                let run = chars
+                    .next()
+                    .and_then(|(_, c)| DIGITS.iter().position(|&d| d == c))
+                    .ok_or_else(|| {
+                        ApgcodeError::invalid(column + 1, "expected a run length after 'y'")
+                    })?;
+                col += run as i64 + 4;
+            }
+            b'z' => {
+                strip += 1;
+                col = 0;
+            }
+            _ => {
+                return Err(ApgcodeError::invalid(
+                    column,
+                    format!("unexpected '{}'", c as char),
+                ))
+            }
+        }
+    }
+
+    Ok(cells)
+}
+
+/// The cells sorted and moved so the object starts at row and column 0.
+fn normalize(cells: &[(i64, i64)]) -> Vec<(i64, i64)> {
+    let (top, left) = top_left(cells);
+    let mut cells: Vec<_> = cells
+        .iter()
+        .map(|&(row, col)| (row - top, col - left))
+        .collect();
+    cells.sort_unstable();
+    cells
+}
+
+/// The topmost row and leftmost column with a live cell.
+fn top_left(cells: &[(i64, i64)]) -> (i64, i64) {
+    let top = cells.iter().map(|&(row, _)| row).min().unwrap_or(0);
+    let left = cells.iter().map(|&(_, col)| col).min().unwrap_or(0);
+    (top, left)
+}
+
+/// Write a phase of an object in one orientation in extended Wechsler
+/// format.
+fn wechsler(cells: &[(i64, i64)], orient: Orientation) -> String {
+    let cells: Vec<_> = cells.iter().map(|&(row, col)| orient(row, col)).collect();
+    let cells: HashSet<_> = normalize(&cells).into_iter().collect();
+    let height = cells.iter().map(|&(row, _)| row + 1).max().unwrap_or(0);
+    let width = cells.iter().map(|&(_, col)| col + 1).max().unwrap_or(0);
+
+    let mut code = String::new();
+    for strip in 0..(height + 4) / 5 {
+        if strip > 0 {
+            code.push('z');
+        }
+
+        // Empty columns are only written once a later column has cells, so
+        // those at the end of a strip are left out.
+        let mut empty = 0;
+        for col in 0..width {
+            let bits = (0..5)
+                .filter(|row| cells.contains(&(strip * 5 + row, col)))
+                .fold(0, |bits, row| bits | 1 << row);
+            if bits == 0 {
+                empty += 1;
+                continue;
+            }
+
+            while empty > 0 {
+                let run = empty.min(39);
+                match run {
+                    1 => code.push('0'),
+                    2 => code.push('w'),
+                    3 => code.push('x'),
+                    _ => {
+                        code.push('y');
+                        code.push(DIGITS[run - 4] as char);
+                    }
+                }
+                empty -= run;
+            }
+            code.push(DIGITS[bits] as char);
+        }
+    }
+    code
+}
+
+/// Reasons an object can't be given an apgcode, or an apgcode can't be
+/// read.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ApgcodeError {
+    /// There were no live cells to name.
+    Empty,
+    /// The object didn't return to its starting shape within the longest
+    /// period looked for.
+    NotPeriodic,
+    /// The rule couldn't be run on an unbounded plane.
+    Rule(RuleError),
+    /// The apgcode is malformed at the given character, counting from 1.
+    Invalid { column: usize, message: String },
+}
+
+impl ApgcodeError {
+    fn invalid(column: usize, message: impl Into<String>) -> ApgcodeError {
+        ApgcodeError::Invalid {
+            column,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ApgcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ApgcodeError::Empty => write!(f, "there are no live cells"),
+            ApgcodeError::NotPeriodic => write!(
+                f,
+                "the object doesn't repeat within {} generations",
+                MAX_PERIOD
+            ),
+            ApgcodeError::Rule(error) => write!(f, "{}", error),
+            ApgcodeError::Invalid { column, message } => {
+                write!(f, "column {}: {}", column, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ApgcodeError {}
+
+impl From<ApgcodeError> for JsValue {
+    fn from(err: ApgcodeError) -> JsValue {
+        JsValue::from_str(&err.to_string())
+    }
+}
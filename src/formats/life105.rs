@@ -0,0 +1,115 @@
+//! The Life 1.05 format, blocks of cells placed relative to the centre of
+//! the pattern.
+//!
+//! ```text
+//! #Life 1.05
+//! #D Glider
+//! #N
+//! #P -1 -1
+//! .*
+//! ..*
+//! ***
+//! ```
+
+use crate::formats::{check_rule, Pattern, PatternError};
+
+pub(crate) fn parse(text: &str) -> Result<Pattern, PatternError> {
+    let mut cells = Vec::new();
+    let mut comments = Vec::new();
+    let mut rule = None;
+    // Where the next row of the current block goes.
+    let (mut row, mut left) = (0i64, 0i64);
+
+    let mut last_line = 1;
+    for (line, text) in text.lines().enumerate() {
+        let line = line + 1;
+        let text = text.trim_end();
+        last_line = line;
+
+        if let Some(directive) = text.strip_prefix('#') {
+            let mut chars = directive.chars();
+            let kind = chars.next();
+            let value = chars.as_str().trim();
+            match kind {
+                Some('D') | Some('C') => comments.push(value.to_string()),
+                Some('N') => rule = Some("B3/S23".to_string()),
+                Some('R') => rule = Some(check_rule(value, line, 4)?),
+                Some('P') => {
+                    let mut coordinates = value.split_whitespace().map(str::parse::<i64>);
+                    match (coordinates.next(), coordinates.next(), coordinates.next()) {
+                        (Some(Ok(x)), Some(Ok(y)), None) => {
+                            row = y;
+                            left = x;
+                        }
+                        _ => {
+                            return Err(PatternError::syntax(line, 4, "expected '#P x y'"));
+                        }
+                    }
+                }
+                _ => (),
+            }
+            continue;
+        }
+
+        let too_large = |column| PatternError::TooLarge { line, column };
+        for (column, c) in text.chars().enumerate() {
+            match c {
+                '.' => (),
+                '*' => {
+                    let col = left
+                        .checked_add(column as i64)
+                        .ok_or_else(|| too_large(column + 1))?;
+                    cells.push((row, col, 1));
+                }
+                _ => {
+                    return Err(PatternError::syntax(
+                        line,
+                        column + 1,
+                        format!("unexpected '{}'", c),
+                    ));
+                }
+            }
+        }
+        row = row.checked_add(1).ok_or_else(|| too_large(1))?;
+    }
+
+    let mut pattern = Pattern::from_cells(cells, last_line)?;
+    pattern.rule = rule;
+    pattern.comments = comments;
+    Ok(pattern)
+}
+
+/// Write the pattern as a single block centred on the origin. Only live
+/// cells are kept.
+pub(crate) fn write(pattern: &Pattern) -> String {
+    let mut text = "#Life 1.05\n".to_string();
+    for description in pattern
+        .name
+        .iter()
+        .chain(&pattern.author)
+        .chain(&pattern.comments)
+    {
+        text += &format!("#D {}\n", description);
+    }
+
+    match pattern.rule.as_deref() {
+        None | Some("B3/S23") => text += "#N\n",
+        Some(rule) => text += &format!("#R {}\n", rule),
+    }
+    text += &format!(
+        "#P {} {}\n",
+        -(pattern.width as i64 / 2),
+        -(pattern.height as i64 / 2)
+    );
+
+    for row in pattern.rows() {
+        let line: String = row
+            .iter()
+            .map(|&state| if state == 1 { '*' } else { '.' })
+            .collect();
+        let line = line.trim_end_matches('.');
+        text += if line.is_empty() { "." } else { line };
+        text.push('\n');
+    }
+    text
+}
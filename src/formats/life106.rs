@@ -0,0 +1,61 @@
+//! The Life 1.06 format, a list of live cells as `x y` coordinates.
+//!
+//! ```text
+//! #Life 1.06
+//! 0 -1
+//! 1 0
+//! -1 1
+//! 0 1
+//! 1 1
+//! ```
+
+use crate::formats::{Pattern, PatternError};
+
+pub(crate) fn parse(text: &str) -> Result<Pattern, PatternError> {
+    let mut cells = Vec::new();
+
+    let mut last_line = 1;
+    for (line, text) in text.lines().enumerate() {
+        let line = line + 1;
+        last_line = line;
+        if text.trim().is_empty() || text.trim_start().starts_with('#') {
+            continue;
+        }
+
+        let mut coordinates = text.split_whitespace().map(|coordinate| {
+            let column = text.find(coordinate).unwrap_or(0) + 1;
+            coordinate.parse::<i64>().map_err(|_| {
+                PatternError::syntax(line, column, format!("invalid coordinate '{}'", coordinate))
+            })
+        });
+        match (coordinates.next(), coordinates.next(), coordinates.next()) {
+            (Some(x), Some(y), None) => cells.push((y?, x?, 1)),
+            (Some(x), Some(y), Some(_)) => {
+                x?;
+                y?;
+                return Err(PatternError::syntax(line, 1, "expected two coordinates"));
+            }
+            _ => return Err(PatternError::syntax(line, 1, "expected two coordinates")),
+        }
+    }
+
+    Pattern::from_cells(cells, last_line)
+}
+
+/// Write the coordinates of every live cell from the top left corner of
+/// the pattern, a row at a time.
+pub(crate) fn write(pattern: &Pattern) -> String {
+    let mut cells: Vec<(u32, u32)> = pattern
+        .cells
+        .iter()
+        .filter(|&&(_, _, state)| state == 1)
+        .map(|&(row, col, _)| (row, col))
+        .collect();
+    cells.sort_unstable();
+
+    let mut text = "#Life 1.06\n".to_string();
+    for (row, col) in cells {
+        text += &format!("{} {}\n", col, row);
+    }
+    text
+}
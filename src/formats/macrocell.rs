@@ -0,0 +1,309 @@
+//! Golly's macrocell format, which writes out the quadtree of a `HashLife`
+//! Universe so each distinct node appears once.
+//!
+//! Leaves are 8 by 8 blocks of `.` and `*` with `$` ending each row, and
+//! each larger node is a line giving its level and the numbers of its four
+//! quadrants, counting nodes from 1 in the order they are written with 0
+//! for an empty quadrant. The last node is the root, centred on row 0,
+//! column 0.
+//!
+//! ```text
+//! [M2] (golly 2.0)
+//! #R B3/S23
+//! $$..*$...*$.***$
+//! 4 1 0 0 0
+//! ```
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+use crate::formats::{Pattern, PatternError};
+use crate::hashlife::{HashLife, NodeId, ALIVE, DEAD};
+use crate::rule::RuleError;
+use crate::topology::Topology;
+
+/// Leaves are 8 by 8 cells.
+const LEAF_LEVEL: u8 = 3;
+const LEAF_SIZE: usize = 1 << LEAF_LEVEL;
+
+pub(crate) fn parse_hashlife(text: &str) -> Result<HashLife, PatternError> {
+    read(text).map(|(hashlife, _)| hashlife)
+}
+
+/// Read the tree along with any topology suffix on the rule, which a
+/// `HashLife` Universe has no use for but a `Pattern` keeps.
+fn read(text: &str) -> Result<(HashLife, Option<String>), PatternError> {
+    let mut hashlife = HashLife::new();
+    let mut suffix = None;
+    let mut nodes: Vec<NodeId> = Vec::new();
+
+    for (line, text) in text.lines().enumerate() {
+        let line = line + 1;
+        let text = text.trim();
+
+        if line == 1 {
+            if !text.starts_with("[M2]") {
+                return Err(PatternError::syntax(line, 1, "expected a '[M2]' header"));
+            }
+            continue;
+        }
+        if let Some(rule) = text.strip_prefix("#R") {
+            let rule = rule.trim();
+            let error = |error| PatternError::Rule {
+                line,
+                column: 4,
+                error,
+            };
+            let (rule, topology) = match rule.split_once(':') {
+                Some((rule, topology)) => (rule, Some(topology)),
+                None => (rule, None),
+            };
+            hashlife.set_rule(rule).map_err(error)?;
+            if let Some(topology) = topology {
+                Topology::parse(topology).map_err(error)?;
+            }
+            suffix = topology.map(|topology| format!(":{}", topology));
+            continue;
+        }
+        if text.is_empty() || text.starts_with('#') {
+            continue;
+        }
+
+        let node = if text.starts_with(['.', '*', '$']) {
+            parse_leaf(&mut hashlife, line, text)?
+        } else {
+            parse_node(&mut hashlife, &nodes, line, text)?
+        };
+        nodes.push(node);
+    }
+
+    if let Some(&root) = nodes.last() {
+        hashlife.set_root(root);
+    }
+    Ok((hashlife, suffix))
+}
+
+/// Parse an 8 by 8 leaf such as `$$..*$...*$.***$`.
+fn parse_leaf(hashlife: &mut HashLife, line: usize, text: &str) -> Result<NodeId, PatternError> {
+    let mut cells = [[false; LEAF_SIZE]; LEAF_SIZE];
+    let (mut row, mut col) = (0, 0);
+
+    for (column, c) in text.chars().enumerate() {
+        match c {
+            '$' => {
+                row += 1;
+                col = 0;
+                continue;
+            }
+            '.' | '*' if row < LEAF_SIZE && col < LEAF_SIZE => cells[row][col] = c == '*',
+            '.' | '*' => {
+                return Err(PatternError::syntax(
+                    line,
+                    column + 1,
+                    "leaves are only 8 by 8 cells",
+                ));
+            }
+            _ => {
+                return Err(PatternError::syntax(
+                    line,
+                    column + 1,
+                    format!("unexpected '{}'", c),
+                ));
+            }
+        }
+        col += 1;
+    }
+
+    Ok(build(hashlife, &cells, LEAF_LEVEL, 0, 0))
+}
+
+/// Build the node covering a square of a leaf's cells.
+fn build(
+    hashlife: &mut HashLife,
+    cells: &[[bool; LEAF_SIZE]; LEAF_SIZE],
+    level: u8,
+    top: usize,
+    left: usize,
+) -> NodeId {
+    if level == 0 {
+        return if cells[top][left] { ALIVE } else { DEAD };
+    }
+
+    let half = 1 << (level - 1);
+    let children = [(0, 0), (0, half), (half, 0), (half, half)]
+        .map(|(row, col)| build(hashlife, cells, level - 1, top + row, left + col));
+    hashlife.join(children)
+}
+
+/// Parse a node line such as `4 1 0 0 2`.
+fn parse_node(
+    hashlife: &mut HashLife,
+    nodes: &[NodeId],
+    line: usize,
+    text: &str,
+) -> Result<NodeId, PatternError> {
+    let mut fields = Vec::new();
+    for field in text.split_whitespace() {
+        let column = text.find(field).unwrap_or(0) + 1;
+        let value = field
+            .parse::<usize>()
+            .map_err(|_| PatternError::syntax(line, column, format!("unexpected '{}'", field)))?;
+        fields.push((value, column));
+    }
+
+    let (level, children) = match fields.as_slice() {
+        [level, children @ ..] if children.len() == 4 => (level, children),
+        _ => {
+            return Err(PatternError::syntax(
+                line,
+                1,
+                "expected a level and four quadrants",
+            ));
+        }
+    };
+    let level = match u8::try_from(level.0) {
+        Ok(level) if level > LEAF_LEVEL && level < 64 => level,
+        // Levels 1 and 2 are only used by rules with more than two states.
+        _ => {
+            return Err(PatternError::syntax(
+                line,
+                level.1,
+                format!("unsupported level {}", level.0),
+            ));
+        }
+    };
+
+    let mut quadrants = [DEAD; 4];
+    for (quadrant, &(index, column)) in quadrants.iter_mut().zip(children) {
+        *quadrant = match index {
+            0 => hashlife.empty(level - 1),
+            _ => match nodes.get(index - 1) {
+                Some(&node) if hashlife.level(node) == level - 1 => node,
+                Some(_) => {
+                    return Err(PatternError::syntax(
+                        line,
+                        column,
+                        format!("node {} is not at level {}", index, level - 1),
+                    ));
+                }
+                None => {
+                    return Err(PatternError::syntax(
+                        line,
+                        column,
+                        format!("node {} is not defined yet", index),
+                    ));
+                }
+            },
+        };
+    }
+
+    Ok(hashlife.join(quadrants))
+}
+
+pub(crate) fn write_hashlife(hashlife: &HashLife) -> String {
+    write_tree(hashlife, &hashlife.rule())
+}
+
+fn write_tree(hashlife: &HashLife, rule: &str) -> String {
+    let mut text = format!("[M2] (wasm-game-of-life)\n#R {}\n", rule);
+    let mut numbers = HashMap::new();
+    write_node(hashlife, hashlife.root(), &mut numbers, &mut text);
+    text
+}
+
+/// Write a node after its quadrants, giving the number it was written
+/// as, or 0 for an empty node.
+fn write_node(
+    hashlife: &HashLife,
+    node: NodeId,
+    numbers: &mut HashMap<NodeId, usize>,
+    text: &mut String,
+) -> usize {
+    if hashlife.is_empty_node(node) {
+        return 0;
+    }
+    if let Some(&number) = numbers.get(&node) {
+        return number;
+    }
+
+    let level = hashlife.level(node);
+    if level == LEAF_LEVEL {
+        let rows: Vec<String> = (0..LEAF_SIZE as i64)
+            .map(|row| {
+                let cells: String = (0..LEAF_SIZE as i64)
+                    .map(|col| {
+                        if hashlife.get_in(node, row, col) {
+                            '*'
+                        } else {
+                            '.'
+                        }
+                    })
+                    .collect();
+                cells.trim_end_matches('.').to_string()
+            })
+            .collect();
+        let last = rows.iter().rposition(|row| !row.is_empty()).unwrap_or(0);
+        for row in &rows[..=last] {
+            text.push_str(row);
+            text.push('$');
+        }
+    } else {
+        let children = hashlife
+            .children(node)
+            .map(|child| write_node(hashlife, child, numbers, text));
+        text.push_str(&format!(
+            "{} {} {} {} {}",
+            level, children[0], children[1], children[2], children[3]
+        ));
+    }
+    text.push('\n');
+
+    let number = numbers.len() + 1;
+    numbers.insert(node, number);
+    number
+}
+
+pub(crate) fn parse(text: &str) -> Result<Pattern, PatternError> {
+    let (hashlife, suffix) = read(text)?;
+    let lines = text.lines().count();
+
+    // Check the size before listing what could be an enormous number of
+    // cells.
+    if let Some(bounds) = hashlife.bounding_box() {
+        let width = u32::try_from(bounds.right - bounds.left + 1);
+        let height = u32::try_from(bounds.bottom - bounds.top + 1);
+        if !matches!((width, height), (Ok(width), Ok(height)) if width.checked_mul(height).is_some())
+        {
+            return Err(PatternError::TooLarge {
+                line: lines,
+                column: 1,
+            });
+        }
+    }
+
+    let cells = hashlife
+        .live_cells()
+        .into_iter()
+        .map(|(row, col)| (row, col, 1))
+        .collect();
+
+    let mut pattern = Pattern::from_cells(cells, lines)?;
+    pattern.rule = Some(hashlife.rule() + suffix.as_deref().unwrap_or_default());
+    Ok(pattern)
+}
+
+/// Write a pattern, refusing rules such as Generations rules that a
+/// `HashLife` Universe can't run.
+pub(crate) fn write(pattern: &Pattern) -> Result<String, RuleError> {
+    let rule = pattern.rule.as_deref().unwrap_or("B3/S23");
+    let mut hashlife = HashLife::new();
+    hashlife.set_rule(rule.split(':').next().unwrap_or_default())?;
+    let cells: Vec<(i64, i64)> = pattern
+        .cells
+        .iter()
+        .filter(|&&(_, _, state)| state == 1)
+        .map(|&(row, col, _)| (row as i64, col as i64))
+        .collect();
+    hashlife.set_cells(&cells);
+    Ok(write_tree(&hashlife, rule))
+}
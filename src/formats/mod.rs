@@ -0,0 +1,312 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use wasm_bindgen::prelude::*;
+
+use crate::rule::{Rule, RuleError};
+use crate::topology::Topology;
+use crate::Universe;
+
+pub(crate) mod life105;
+pub(crate) mod life106;
+pub(crate) mod macrocell;
+pub(crate) mod plaintext;
+pub(crate) mod rle;
+
+/// The pattern file formats that can be read and written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// Run length encoded, as used by Golly and the LifeWiki.
+    Rle,
+    /// The `.cells` plaintext grid of `.` and `O`.
+    Plaintext,
+    /// Life 1.05, blocks of `.` and `*` placed with `#P` lines.
+    Life105,
+    /// Life 1.06, a list of live cell coordinates.
+    Life106,
+    /// Golly's macrocell format, a quadtree of 8 by 8 leaves.
+    Macrocell,
+}
+
+impl Format {
+    /// Guess the format of a pattern file from its contents.
+    pub fn detect(text: &str) -> Option<Format> {
+        let first = text.trim_start();
+        if first.starts_with("#Life 1.06") {
+            return Some(Format::Life106);
+        }
+        if first.starts_with("#Life 1.05") {
+            return Some(Format::Life105);
+        }
+        if first.starts_with("[M2]") {
+            return Some(Format::Macrocell);
+        }
+
+        let line = text
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty() && !line.starts_with('#'))?;
+        if line.starts_with('!') {
+            Some(Format::Plaintext)
+        } else if line.starts_with('x') && line.contains('=') {
+            Some(Format::Rle)
+        } else if line.chars().all(|c| matches!(c, '.' | 'O' | '*')) {
+            Some(Format::Plaintext)
+        } else if line
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '$' | '!' | ' '))
+        {
+            Some(Format::Rle)
+        } else {
+            None
+        }
+    }
+}
+
+/// A pattern independent of any `Universe`, as read from or written to a
+/// pattern file.
+///
+/// Cells are placed from the top left corner of a `width` by `height`
+/// rectangle. Life 1.05, Life 1.06 and macrocell files have no size of their
+/// own, so patterns read from them are sized to fit their cells.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Pattern {
+    pub name: Option<String>,
+    pub author: Option<String>,
+    pub comments: Vec<String>,
+    /// The rulestring, with any topology suffix.
+    pub rule: Option<String>,
+    pub width: u32,
+    pub height: u32,
+    /// The row, column and state of every cell that isn't dead.
+    pub cells: Vec<(u32, u32, u8)>,
+}
+
+impl Pattern {
+    /// Read a pattern, working out its format from the contents.
+    pub fn parse(text: &str) -> Result<Pattern, PatternError> {
+        let format = Format::detect(text)
+            .ok_or_else(|| PatternError::syntax(1, 1, "unrecognised pattern format"))?;
+        Pattern::parse_as(text, format)
+    }
+
+    pub fn parse_as(text: &str, format: Format) -> Result<Pattern, PatternError> {
+        match format {
+            Format::Rle => rle::parse(text),
+            Format::Plaintext => plaintext::parse(text),
+            Format::Life105 => life105::parse(text),
+            Format::Life106 => life106::parse(text),
+            Format::Macrocell => macrocell::parse(text),
+        }
+    }
+
+    /// Write the pattern in a format.
+    ///
+    /// Only macrocell files can fail, as they are only written for rules
+    /// that a `HashLife` Universe can run.
+    pub fn write(&self, format: Format) -> Result<String, RuleError> {
+        match format {
+            Format::Rle => Ok(rle::write(self)),
+            Format::Plaintext => Ok(plaintext::write(self)),
+            Format::Life105 => Ok(life105::write(self)),
+            Format::Life106 => Ok(life106::write(self)),
+            Format::Macrocell => macrocell::write(self),
+        }
+    }
+
+    /// Every cell of a Universe that isn't dead, along with its size and
+    /// rule.
+    ///
+    /// A loaded `.rule` file is only kept as its name, which `to_universe`
+    /// and deserializing can't turn back into a rule.
+    pub fn from_universe(universe: &Universe) -> Pattern {
+        let (width, height) = (universe.width(), universe.height());
+        let cells = (0..height)
+            .flat_map(|row| (0..width).map(move |col| (row, col)))
+            .map(|(row, col)| (row, col, universe.get_state(row, col)))
+            .filter(|&(_, _, state)| state != 0)
+            .collect();
+
+        Pattern {
+            rule: Some(universe.rule()),
+            width,
+            height,
+            cells,
+            ..Pattern::default()
+        }
+    }
+
+    /// A Universe the size of the pattern holding its cells, under the
+    /// pattern's rule or Conway's Life if it has none.
+    ///
+    /// Fails if the pattern is too large for a Universe or its rule can't
+    /// be parsed, which can only happen to a pattern that wasn't read from
+    /// a file. The error then points at the first line.
+    pub fn to_universe(&self) -> Result<Universe, PatternError> {
+        if !Universe::fits(self.width, self.height) {
+            return Err(PatternError::TooLarge { line: 1, column: 1 });
+        }
+
+        let mut universe = Universe::empty(self.width, self.height);
+        if let Some(rule) = &self.rule {
+            universe
+                .set_rule(rule)
+                .map_err(|error| PatternError::Rule {
+                    line: 1,
+                    column: 1,
+                    error,
+                })?;
+        }
+
+        universe.load_cells(0, 0, &self.cells);
+        Ok(universe)
+    }
+
+    /// The number of states under the pattern's rule, counting any states
+    /// its cells are in beyond that.
+    pub(crate) fn state_count(&self) -> u8 {
+        let rule = self.rule.as_deref().unwrap_or_default();
+        let rule = rule.split(':').next().unwrap_or_default();
+        let states = Rule::parse(rule).map_or(2, |rule| rule.states());
+        let highest = self
+            .cells
+            .iter()
+            .map(|&(_, _, state)| state)
+            .max()
+            .unwrap_or(0);
+        states.max(highest.saturating_add(1))
+    }
+
+    /// The state of every cell, a row at a time.
+    pub(crate) fn rows(&self) -> Vec<Vec<u8>> {
+        let mut rows = vec![vec![0; self.width as usize]; self.height as usize];
+        for &(row, col, state) in &self.cells {
+            if let Some(cell) = rows
+                .get_mut(row as usize)
+                .and_then(|row| row.get_mut(col as usize))
+            {
+                *cell = state;
+            }
+        }
+        rows
+    }
+
+    /// Move the cells so the pattern starts at its top and leftmost cells
+    /// and size it to fit, from cells at any row and column.
+    pub(crate) fn from_cells(
+        cells: Vec<(i64, i64, u8)>,
+        line: usize,
+    ) -> Result<Pattern, PatternError> {
+        let top = cells.iter().map(|&(row, _, _)| row).min().unwrap_or(0);
+        let left = cells.iter().map(|&(_, col, _)| col).min().unwrap_or(0);
+        // A cell on the last row or column an i64 can hold has no end.
+        let bottom = cells
+            .iter()
+            .map(|&(row, _, _)| row)
+            .max()
+            .unwrap_or(-1)
+            .checked_add(1);
+        let right = cells
+            .iter()
+            .map(|&(_, col, _)| col)
+            .max()
+            .unwrap_or(-1)
+            .checked_add(1);
+
+        let size = |start: i64, end: Option<i64>| {
+            end.and_then(|end| end.checked_sub(start))
+                .and_then(|size| u32::try_from(size).ok())
+        };
+        let (width, height) = match (size(left, right), size(top, bottom)) {
+            (Some(width), Some(height)) if Universe::fits(width, height) => (width, height),
+            _ => return Err(PatternError::TooLarge { line, column: 1 }),
+        };
+
+        Ok(Pattern {
+            width,
+            height,
+            cells: cells
+                .into_iter()
+                .map(|(row, col, state)| ((row - top) as u32, (col - left) as u32, state))
+                .collect(),
+            ..Pattern::default()
+        })
+    }
+}
+
+/// Check a rule and any topology suffix found at the given line and column
+/// without applying them.
+pub(crate) fn check_rule(rule: &str, line: usize, column: usize) -> Result<String, PatternError> {
+    let (parsed, suffix) = match rule.split_once(':') {
+        Some((rule, suffix)) => (rule, Some(suffix)),
+        None => (rule, None),
+    };
+
+    Rule::parse(parsed)
+        .and_then(|_| suffix.map(Topology::parse).transpose())
+        .map(|_| rule.to_string())
+        .map_err(|error| PatternError::Rule {
+            line,
+            column,
+            error,
+        })
+}
+
+/// Why a pattern file couldn't be read, with the line and column it went
+/// wrong at, both counting from 1.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PatternError {
+    /// The text doesn't follow the format.
+    Syntax {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    /// The rule named by the pattern couldn't be parsed.
+    Rule {
+        line: usize,
+        column: usize,
+        error: RuleError,
+    },
+    /// The pattern is too big to fit in a `Universe`.
+    TooLarge { line: usize, column: usize },
+}
+
+impl PatternError {
+    pub(crate) fn syntax(line: usize, column: usize, message: impl Into<String>) -> PatternError {
+        PatternError::Syntax {
+            line,
+            column,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PatternError::Syntax {
+                line,
+                column,
+                message,
+            } => write!(f, "line {}, column {}: {}", line, column, message),
+            PatternError::Rule {
+                line,
+                column,
+                error,
+            } => write!(f, "line {}, column {}: {}", line, column, error),
+            PatternError::TooLarge { line, column } => {
+                write!(f, "line {}, column {}: pattern is too large", line, column)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PatternError {}
+
+impl From<PatternError> for JsValue {
+    fn from(err: PatternError) -> JsValue {
+        JsValue::from_str(&err.to_string())
+    }
+}
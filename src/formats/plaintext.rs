@@ -0,0 +1,87 @@
+//! The plaintext `.cells` format used by the LifeWiki.
+//!
+//! ```text
+//! !Name: Glider
+//! .O.
+//! ..O
+//! OOO
+//! ```
+
+use std::convert::TryFrom;
+
+use crate::formats::{Pattern, PatternError};
+
+pub(crate) fn parse(text: &str) -> Result<Pattern, PatternError> {
+    let mut pattern = Pattern::default();
+    let (mut rows, mut width) = (0usize, 0usize);
+    let mut last_line = 0;
+
+    for (line, text) in text.lines().enumerate() {
+        let line = line + 1;
+        let text = text.trim_end();
+
+        if let Some(comment) = text.strip_prefix('!') {
+            let comment = comment.trim();
+            if let Some(name) = comment.strip_prefix("Name:") {
+                pattern.name = Some(name.trim().to_string());
+            } else if let Some(author) = comment.strip_prefix("Author:") {
+                pattern.author = Some(author.trim().to_string());
+            } else {
+                pattern.comments.push(comment.to_string());
+            }
+            continue;
+        }
+
+        for (column, c) in text.chars().enumerate() {
+            match c {
+                '.' => (),
+                'O' | '*' => pattern.cells.push((rows as u32, column as u32, 1)),
+                _ => {
+                    return Err(PatternError::syntax(
+                        line,
+                        column + 1,
+                        format!("unexpected '{}'", c),
+                    ));
+                }
+            }
+        }
+
+        rows += 1;
+        width = width.max(text.chars().count());
+        // Blank lines are empty rows, except after the last row of cells.
+        if !text.is_empty() {
+            pattern.height = rows as u32;
+            last_line = line;
+        }
+    }
+
+    pattern.width = u32::try_from(width)
+        .ok()
+        .filter(|width| width.checked_mul(pattern.height).is_some())
+        .ok_or(PatternError::TooLarge {
+            line: last_line,
+            column: 1,
+        })?;
+    Ok(pattern)
+}
+
+/// Write the pattern as rows of `.` and `O`. Only live cells are kept,
+/// dying cells of Generations rules are written as dead.
+pub(crate) fn write(pattern: &Pattern) -> String {
+    let mut text = String::new();
+    if let Some(name) = &pattern.name {
+        text += &format!("!Name: {}\n", name);
+    }
+    if let Some(author) = &pattern.author {
+        text += &format!("!Author: {}\n", author);
+    }
+    for comment in &pattern.comments {
+        text += &format!("!{}\n", comment);
+    }
+
+    for row in pattern.rows() {
+        text.extend(row.iter().map(|&state| if state == 1 { 'O' } else { '.' }));
+        text.push('\n');
+    }
+    text
+}
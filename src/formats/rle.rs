@@ -0,0 +1,312 @@
+//! The run length encoded format used by Golly and the LifeWiki.
+//!
+//! ```text
+//! #N Glider
+//! x = 3, y = 3, rule = B3/S23
+//! bob$2bo$3o!
+//! ```
+
+use crate::formats::{check_rule, Pattern, PatternError};
+use crate::Universe;
+
+/// Lines are wrapped before they grow past this many characters.
+const LINE_LENGTH: usize = 70;
+
+pub(crate) fn parse(text: &str) -> Result<Pattern, PatternError> {
+    let mut pattern = Pattern::default();
+    let mut header = None;
+    let mut cells = Vec::new();
+    let (mut row, mut col) = (0u32, 0u32);
+    let (mut width, mut height) = (0, 0);
+
+    let mut count: Option<u32> = None;
+    let mut prefix: Option<u8> = None;
+
+    'lines: for (line, text) in text.lines().enumerate() {
+        let line = line + 1;
+        let trimmed = text.trim_start();
+
+        if let Some(comment) = trimmed.strip_prefix('#') {
+            let mut chars = comment.chars();
+            let kind = chars.next();
+            let value = chars.as_str().trim().to_string();
+            match kind {
+                Some('N') => pattern.name = Some(value),
+                Some('O') => pattern.author = Some(value),
+                Some('C') | Some('c') => pattern.comments.push(value),
+                _ => (),
+            }
+            continue;
+        }
+        if header.is_none() && cells.is_empty() && trimmed.starts_with('x') {
+            header = Some((line, parse_header(line, text)?));
+            continue;
+        }
+
+        for (column, c) in text.chars().enumerate() {
+            let column = column + 1;
+            let too_large = || PatternError::TooLarge { line, column };
+
+            if let Some(digit) = c.to_digit(10) {
+                let run = count
+                    .unwrap_or(0)
+                    .checked_mul(10)
+                    .and_then(|run| run.checked_add(digit));
+                count = Some(run.ok_or_else(too_large)?);
+                continue;
+            }
+            if c.is_whitespace() {
+                continue;
+            }
+
+            // States above 24 are written as a letter from `p` to `y`
+            // followed by one from `A` to `X`, after any run count.
+            if prefix.is_some() && !matches!(c, 'A'..='X') {
+                return Err(PatternError::syntax(
+                    line,
+                    column,
+                    "expected a state letter after its prefix",
+                ));
+            }
+            if let 'p'..='y' = c {
+                prefix = Some(c as u8 - b'p' + 1);
+                continue;
+            }
+
+            let run = match count.take() {
+                Some(0) => {
+                    return Err(PatternError::syntax(
+                        line,
+                        column,
+                        "runs must have at least one cell",
+                    ))
+                }
+                Some(run) => run,
+                None => 1,
+            };
+
+            match c {
+                '!' => break 'lines,
+                '$' => {
+                    row = row.checked_add(run).ok_or_else(too_large)?;
+                    col = 0;
+                }
+                'b' | '.' => col = col.checked_add(run).ok_or_else(too_large)?,
+                'o' | 'A'..='X' => {
+                    let letter = if c == 'o' {
+                        1
+                    } else {
+                        c as u32 - 'A' as u32 + 1
+                    };
+                    let state = prefix.take().map_or(0, |prefix| prefix as u32 * 24) + letter;
+                    if state > u8::MAX as u32 {
+                        return Err(PatternError::syntax(
+                            line,
+                            column,
+                            format!("state {} is too high", state),
+                        ));
+                    }
+
+                    // Refuse a long run before allocating its cells. There
+                    // can't be more live cells than the header's size, or
+                    // than a Universe can hold.
+                    let end = col.checked_add(run).ok_or_else(too_large)?;
+                    let limit = match header {
+                        Some((_, (width, height, _))) if width > 0 && height > 0 => {
+                            width as u64 * height as u64
+                        }
+                        _ => u32::MAX as u64,
+                    };
+                    if cells.len() as u64 + run as u64 > limit {
+                        return Err(too_large());
+                    }
+                    cells.extend((col..end).map(|col| (row, col, state as u8)));
+                    col = end;
+                    width = width.max(end);
+                    height = row.checked_add(1).ok_or_else(too_large)?;
+                }
+                _ => {
+                    return Err(PatternError::syntax(
+                        line,
+                        column,
+                        format!("unexpected '{}'", c),
+                    ));
+                }
+            }
+        }
+    }
+
+    let (line, (header_width, header_height, rule)) = header.unwrap_or((1, (0, 0, None)));
+    let (width, height) = (width.max(header_width), height.max(header_height));
+    if !Universe::fits(width, height) {
+        return Err(PatternError::TooLarge { line, column: 1 });
+    }
+
+    pattern.width = width;
+    pattern.height = height;
+    pattern.rule = rule;
+    pattern.cells = cells;
+    Ok(pattern)
+}
+
+/// Parse a header line such as `x = 3, y = 3, rule = B3/S23`.
+fn parse_header(line: usize, text: &str) -> Result<(u32, u32, Option<String>), PatternError> {
+    // The rule runs to the end of the line, as some rules contain commas.
+    let (fields, rule) = match text.find("rule") {
+        Some(start) => (&text[..start], Some(start)),
+        None => (text, None),
+    };
+
+    let (mut width, mut height) = (None, None);
+    let mut offset = 0;
+    for field in fields.split(',') {
+        let column = offset + field.len() - field.trim_start().len() + 1;
+        offset += field.len() + 1;
+        if field.trim().is_empty() {
+            continue;
+        }
+
+        let (key, value) = field.split_once('=').ok_or_else(|| {
+            PatternError::syntax(
+                line,
+                column,
+                format!("expected 'key = value', found '{}'", field.trim()),
+            )
+        })?;
+        let value = value.trim().parse::<u32>().map_err(|_| {
+            PatternError::syntax(line, column, format!("invalid size '{}'", value.trim()))
+        })?;
+
+        match key.trim() {
+            "x" => width = Some(value),
+            "y" => height = Some(value),
+            key => {
+                return Err(PatternError::syntax(
+                    line,
+                    column,
+                    format!("unknown header field '{}'", key),
+                ));
+            }
+        }
+    }
+
+    let rule = match rule {
+        Some(start) => {
+            let value = text[start + "rule".len()..].trim_start();
+            let value = value
+                .strip_prefix('=')
+                .ok_or_else(|| PatternError::syntax(line, start + 1, "expected 'rule = '"))?
+                .trim();
+            Some(check_rule(value, line, start + 1)?)
+        }
+        None => None,
+    };
+
+    match (width, height) {
+        (Some(width), Some(height)) => Ok((width, height, rule)),
+        _ => Err(PatternError::syntax(
+            line,
+            1,
+            "the header needs both x and y",
+        )),
+    }
+}
+
+/// The tag for a run of cells in a state, `b` and `o` for two-state rules
+/// and `.`, `A`, `B` and so on otherwise.
+fn tag(state: u8, multi_state: bool) -> String {
+    match (state, multi_state) {
+        (0, false) => "b".to_string(),
+        (_, false) => "o".to_string(),
+        (0, true) => ".".to_string(),
+        (1..=24, true) => ((b'A' + state - 1) as char).to_string(),
+        (_, true) => {
+            let prefix = (b'p' + (state - 25) / 24) as char;
+            let letter = (b'A' + (state - 25) % 24) as char;
+            format!("{}{}", prefix, letter)
+        }
+    }
+}
+
+/// Collects runs into lines no longer than `LINE_LENGTH`.
+struct Wrapper {
+    text: String,
+    line: usize,
+}
+
+impl Wrapper {
+    fn push(&mut self, run: u32, tag: &str) {
+        let token = if run == 1 {
+            tag.to_string()
+        } else {
+            format!("{}{}", run, tag)
+        };
+
+        if self.line + token.len() > LINE_LENGTH {
+            self.text.push('\n');
+            self.line = 0;
+        }
+        self.text.push_str(&token);
+        self.line += token.len();
+    }
+}
+
+pub(crate) fn write(pattern: &Pattern) -> String {
+    let mut text = String::new();
+    if let Some(name) = &pattern.name {
+        text += &format!("#N {}\n", name);
+    }
+    if let Some(author) = &pattern.author {
+        text += &format!("#O {}\n", author);
+    }
+    for comment in &pattern.comments {
+        text += &format!("#C {}\n", comment);
+    }
+    text += &format!(
+        "x = {}, y = {}, rule = {}\n",
+        pattern.width,
+        pattern.height,
+        pattern.rule.as_deref().unwrap_or("B3/S23")
+    );
+    text + &write_cells(pattern)
+}
+
+/// Write just the runs of cells, without any comments or header.
+pub(crate) fn write_cells(pattern: &Pattern) -> String {
+    let multi_state = pattern.state_count() > 2;
+    let mut body = Wrapper {
+        text: String::new(),
+        line: 0,
+    };
+
+    // Row ends are held back until the next row with live cells, so runs
+    // of empty rows collapse into one and trailing ones are dropped.
+    let mut row_ends = 0;
+    for row in pattern.rows() {
+        let mut runs: Vec<(u32, u8)> = Vec::new();
+        for state in row {
+            match runs.last_mut() {
+                Some((run, last)) if *last == state => *run += 1,
+                _ => runs.push((1, state)),
+            }
+        }
+        if runs.last().is_some_and(|&(_, state)| state == 0) {
+            runs.pop();
+        }
+
+        if !runs.is_empty() {
+            if row_ends > 0 {
+                body.push(row_ends, "$");
+            }
+            for (run, state) in runs {
+                body.push(run, &tag(state, multi_state));
+            }
+            row_ends = 0;
+        }
+        row_ends += 1;
+    }
+
+    body.push(1, "!");
+    body.text.push('\n');
+    body.text
+}
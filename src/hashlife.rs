@@ -0,0 +1,684 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use wasm_bindgen::prelude::*;
+
+use crate::formats::{macrocell, PatternError};
+use crate::rule::{Rule, RuleError};
+use crate::sparse::BoundingBox;
+use crate::Universe;
+
+pub(crate) type NodeId = u32;
+
+pub(crate) const DEAD: NodeId = 0;
+pub(crate) const ALIVE: NodeId = 1;
+
+/// The smallest root the quadtree shrinks to, 8 by 8 cells.
+const MIN_ROOT_LEVEL: u8 = 3;
+
+/// The largest root the quadtree grows to, whose half width is the largest
+/// power of two an `i64` holds.
+const MAX_ROOT_LEVEL: u8 = 63;
+
+/// The largest `k` that `HashLife::step_pow2` takes, since stepping by
+/// `2^k` generations needs a root of at least level `k + 3`.
+pub const MAX_STEP: u8 = MAX_ROOT_LEVEL - 3;
+
+/// A square of `2^level` cells split into four quadrants. Nodes are
+/// canonical, so two nodes with the same contents always share an id.
+#[derive(Clone, Copy, Debug)]
+struct Node {
+    /// North-west, north-east, south-west and south-east quadrants. Unused
+    /// for single cells at level 0.
+    children: [NodeId; 4],
+    level: u8,
+    population: u64,
+}
+
+/// A live cell fell outside the Universe a pattern was being flattened into,
+/// or the Universe was too large to make.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DoesNotFit {
+    /// The live cells, if there are any.
+    pub bounds: Option<BoundingBox>,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl fmt::Display for DoesNotFit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.bounds {
+            Some(bounds) if Universe::fits(self.width, self.height) => write!(
+                f,
+                "pattern spanning rows {} to {} and columns {} to {} does not fit in a {}x{} Universe",
+                bounds.top,
+                bounds.bottom,
+                bounds.left,
+                bounds.right,
+                self.width,
+                self.height
+            ),
+            _ => write!(
+                f,
+                "a {}x{} Universe has too many cells",
+                self.width, self.height
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DoesNotFit {}
+
+impl From<DoesNotFit> for JsValue {
+    fn from(err: DoesNotFit) -> JsValue {
+        JsValue::from_str(&err.to_string())
+    }
+}
+
+/// An unbounded Universe evolved with Gosper's HashLife algorithm.
+///
+/// The plane is held as a quadtree of canonical nodes, and the result of
+/// advancing each node is memoised, so repetitive patterns can be run for
+/// millions of generations or more. The root is always centred on row 0,
+/// column 0.
+///
+/// Like `SparseUniverse`, only two-state rules on the immediate
+/// neighbourhood are supported, and not those where cells are born with no
+/// live neighbours.
+#[wasm_bindgen]
+pub struct HashLife {
+    nodes: Vec<Node>,
+    lookup: HashMap<[NodeId; 4], NodeId>,
+    /// Memoised results, keyed by node and the log2 of the generations.
+    results: HashMap<(NodeId, u8), NodeId>,
+    /// The empty node of each level.
+    empty: Vec<NodeId>,
+    root: NodeId,
+    rule: Rule,
+    generation: u64,
+    max_nodes: usize,
+}
+
+impl HashLife {
+    /// Set cells to be alive by passing the row and column of each cell as
+    /// an array.
+    pub fn set_cells(&mut self, cells: &[(i64, i64)]) {
+        for (row, col) in cells.iter().cloned() {
+            self.set_cell(row, col, true);
+        }
+    }
+
+    /// Every live cell as a row and column.
+    pub fn live_cells(&self) -> Vec<(i64, i64)> {
+        let mut cells = Vec::new();
+        let half = self.half_width(self.root);
+        self.collect_cells(self.root, -half, -half, &mut cells);
+        cells
+    }
+
+    /// Create a HashLife Universe holding the live cells of a `Universe`,
+    /// at the same rows and columns and with the same rule.
+    ///
+    /// The Universe's edges are not carried over, so patterns that touch
+    /// them will evolve differently on the unbounded plane.
+    pub fn from_universe(universe: &Universe) -> Result<HashLife, RuleError> {
+        let mut hashlife = HashLife::new();
+        hashlife.set_rule(&universe.rule_name())?;
+
+        let cells = universe.get_cells();
+        for row in 0..universe.height() {
+            for col in 0..universe.width() {
+                if cells[(row * universe.width() + col) as usize] {
+                    hashlife.set_cell(row as i64, col as i64, true);
+                }
+            }
+        }
+
+        Ok(hashlife)
+    }
+
+    /// Flatten the live cells into a new `width` by `height` Universe,
+    /// keeping their rows and columns.
+    pub fn to_universe(&self, width: u32, height: u32) -> Result<Universe, DoesNotFit> {
+        if !Universe::fits(width, height) {
+            return Err(DoesNotFit {
+                bounds: self.bounding_box(),
+                width,
+                height,
+            });
+        }
+
+        let mut universe = Universe::empty(width, height);
+        universe
+            .set_rule(&self.rule.to_string())
+            .expect("HashLife rules are valid");
+
+        if let Some(bounds) = self.bounding_box() {
+            if bounds.top < 0
+                || bounds.left < 0
+                || bounds.bottom >= height as i64
+                || bounds.right >= width as i64
+            {
+                return Err(DoesNotFit {
+                    bounds: Some(bounds),
+                    width,
+                    height,
+                });
+            }
+        }
+
+        let cells: Vec<(u32, u32)> = self
+            .live_cells()
+            .into_iter()
+            .map(|(row, col)| (row as u32, col as u32))
+            .collect();
+        universe.set_cells(&cells);
+
+        Ok(universe)
+    }
+
+    pub(crate) fn root(&self) -> NodeId {
+        self.root
+    }
+
+    /// Replace the whole pattern with a node of at least 8 by 8 cells,
+    /// centred on row 0, column 0.
+    pub(crate) fn set_root(&mut self, id: NodeId) {
+        debug_assert!(self.level(id) >= MIN_ROOT_LEVEL);
+        self.root = id;
+        self.crop();
+    }
+
+    pub(crate) fn is_empty_node(&self, id: NodeId) -> bool {
+        self.node(id).population == 0
+    }
+
+    fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id as usize]
+    }
+
+    pub(crate) fn children(&self, id: NodeId) -> [NodeId; 4] {
+        self.node(id).children
+    }
+
+    pub(crate) fn level(&self, id: NodeId) -> u8 {
+        self.node(id).level
+    }
+
+    /// Half the width of a node in cells.
+    fn half_width(&self, id: NodeId) -> i64 {
+        1 << (self.level(id) - 1)
+    }
+
+    /// The canonical node with the given quadrants.
+    pub(crate) fn join(&mut self, children: [NodeId; 4]) -> NodeId {
+        if let Some(&id) = self.lookup.get(&children) {
+            return id;
+        }
+
+        let level = self.level(children[0]) + 1;
+        let population = children.iter().fold(0u64, |population, &child| {
+            population.saturating_add(self.node(child).population)
+        });
+
+        let id = self.nodes.len() as NodeId;
+        self.nodes.push(Node {
+            children,
+            level,
+            population,
+        });
+        self.lookup.insert(children, id);
+        id
+    }
+
+    /// The empty node covering `2^level` cells.
+    pub(crate) fn empty(&mut self, level: u8) -> NodeId {
+        while self.empty.len() <= level as usize {
+            let below = *self.empty.last().expect("level 0 is always present");
+            let id = self.join([below; 4]);
+            self.empty.push(id);
+        }
+        self.empty[level as usize]
+    }
+
+    /// A node twice the size with `id` in the middle and empty space around.
+    fn centre(&mut self, id: NodeId) -> NodeId {
+        let [nw, ne, sw, se] = self.children(id);
+        let empty = self.empty(self.level(id) - 1);
+
+        let nw = self.join([empty, empty, empty, nw]);
+        let ne = self.join([empty, empty, ne, empty]);
+        let sw = self.join([empty, sw, empty, empty]);
+        let se = self.join([se, empty, empty, empty]);
+        self.join([nw, ne, sw, se])
+    }
+
+    /// The node of half the size in the middle of `id`.
+    fn inner(&mut self, id: NodeId) -> NodeId {
+        let [nw, ne, sw, se] = self.children(id);
+        self.join([
+            self.children(nw)[3],
+            self.children(ne)[2],
+            self.children(sw)[1],
+            self.children(se)[0],
+        ])
+    }
+
+    fn set_cell(&mut self, row: i64, col: i64, alive: bool) {
+        loop {
+            let half = self.half_width(self.root);
+            if (-half..half).contains(&row) && (-half..half).contains(&col) {
+                break;
+            }
+            self.root = self.centre(self.root);
+        }
+
+        let half = self.half_width(self.root);
+        self.root = self.set_in(self.root, row + half, col + half, alive);
+    }
+
+    fn set_in(&mut self, id: NodeId, row: i64, col: i64, alive: bool) -> NodeId {
+        if self.level(id) == 0 {
+            return if alive { ALIVE } else { DEAD };
+        }
+
+        let half = self.half_width(id);
+        let quadrant = ((row >= half) as usize) * 2 + (col >= half) as usize;
+        let mut children = self.children(id);
+        children[quadrant] = self.set_in(children[quadrant], row % half, col % half, alive);
+        self.join(children)
+    }
+
+    pub(crate) fn get_in(&self, id: NodeId, row: i64, col: i64) -> bool {
+        if self.level(id) == 0 {
+            return id == ALIVE;
+        }
+
+        let half = self.half_width(id);
+        let quadrant = ((row >= half) as usize) * 2 + (col >= half) as usize;
+        self.get_in(self.children(id)[quadrant], row % half, col % half)
+    }
+
+    fn collect_cells(&self, id: NodeId, top: i64, left: i64, cells: &mut Vec<(i64, i64)>) {
+        let node = self.node(id);
+        if node.population == 0 {
+            return;
+        }
+        if node.level == 0 {
+            cells.push((top, left));
+            return;
+        }
+
+        let half = self.half_width(id);
+        for (quadrant, &child) in node.children.iter().enumerate() {
+            let row = top + half * (quadrant as i64 / 2);
+            let col = left + half * (quadrant as i64 % 2);
+            self.collect_cells(child, row, col, cells);
+        }
+    }
+
+    /// The first (or with `last`, final) row or column of a node holding a
+    /// live cell, relative to the node's corner.
+    fn extent(
+        &self,
+        id: NodeId,
+        column: bool,
+        last: bool,
+        memo: &mut HashMap<NodeId, Option<i64>>,
+    ) -> Option<i64> {
+        if self.node(id).population == 0 {
+            return None;
+        }
+        if self.level(id) == 0 {
+            return Some(0);
+        }
+        if let Some(&extent) = memo.get(&id) {
+            return extent;
+        }
+
+        let half = self.half_width(id);
+        let extent = self
+            .children(id)
+            .iter()
+            .enumerate()
+            .filter_map(|(quadrant, &child)| {
+                let offset = if column { quadrant % 2 } else { quadrant / 2 } as i64 * half;
+                self.extent(child, column, last, memo)
+                    .map(|extent| offset + extent)
+            })
+            .reduce(|a, b| if last { a.max(b) } else { a.min(b) });
+
+        memo.insert(id, extent);
+        extent
+    }
+
+    /// The next generation of the middle 2 by 2 cells of a 4 by 4 node.
+    fn step_leaf(&mut self, id: NodeId) -> NodeId {
+        let mut cells = [[false; 4]; 4];
+        for (row, cells) in cells.iter_mut().enumerate() {
+            for (col, cell) in cells.iter_mut().enumerate() {
+                *cell = self.get_in(id, row as i64, col as i64);
+            }
+        }
+
+        let mut next = [DEAD; 4];
+        for (quadrant, cell) in next.iter_mut().enumerate() {
+            let (row, col) = (1 + quadrant / 2, 1 + quadrant % 2);
+            let neighbourhood = crate::NEIGHBOURS.iter().enumerate().fold(
+                0u8,
+                |mask, (bit, &(delta_row, delta_col))| {
+                    let alive =
+                        cells[(row as i64 + delta_row) as usize][(col as i64 + delta_col) as usize];
+                    mask | (alive as u8) << bit
+                },
+            );
+
+            if self.rule.next_state(cells[row][col], neighbourhood) {
+                *cell = ALIVE;
+            }
+        }
+
+        self.join(next)
+    }
+
+    /// The middle half of a node advanced by `2^step` generations, where
+    /// `step` is at most the node's level minus two.
+    fn successor(&mut self, id: NodeId, step: u8) -> NodeId {
+        let level = self.level(id);
+        let step = step.min(level - 2);
+
+        if self.node(id).population == 0 {
+            return self.empty(level - 1);
+        }
+        if let Some(&result) = self.results.get(&(id, step)) {
+            return result;
+        }
+
+        let result = if level == 2 {
+            self.step_leaf(id)
+        } else {
+            // Split the node into a 3 by 3 grid of overlapping quarter size
+            // nodes and advance each one.
+            let [nw, ne, sw, se] = self.children(id);
+            let [_, nw_ne, nw_sw, nw_se] = self.children(nw);
+            let [ne_nw, _, ne_sw, ne_se] = self.children(ne);
+            let [sw_nw, sw_ne, _, sw_se] = self.children(sw);
+            let [se_nw, se_ne, se_sw, _] = self.children(se);
+
+            let grid = [
+                nw,
+                self.join([nw_ne, ne_nw, nw_se, ne_sw]),
+                ne,
+                self.join([nw_sw, nw_se, sw_nw, sw_ne]),
+                self.join([nw_se, ne_sw, sw_ne, se_nw]),
+                self.join([ne_sw, ne_se, se_nw, se_ne]),
+                sw,
+                self.join([sw_ne, se_nw, sw_se, se_sw]),
+                se,
+            ];
+
+            let mut advanced = [DEAD; 9];
+            for (i, &node) in grid.iter().enumerate() {
+                advanced[i] = self.successor(node, step);
+            }
+
+            let quadrants = [[0, 1, 3, 4], [1, 2, 4, 5], [3, 4, 6, 7], [4, 5, 7, 8]];
+            let mut result = [DEAD; 4];
+            for (quadrant, corners) in result.iter_mut().zip(quadrants.iter()) {
+                let [a, b, c, d] = corners.map(|i| advanced[i]);
+                *quadrant = if step == level - 2 {
+                    // Full speed: the second half of the generations comes
+                    // from advancing the combined results again.
+                    let combined = self.join([a, b, c, d]);
+                    self.successor(combined, step)
+                } else {
+                    // The generations are already done, so just take the
+                    // middle of the combined results.
+                    self.join([
+                        self.children(a)[3],
+                        self.children(b)[2],
+                        self.children(c)[1],
+                        self.children(d)[0],
+                    ])
+                };
+            }
+
+            self.join(result)
+        };
+
+        self.results.insert((id, step), result);
+        result
+    }
+
+    /// Whether every live cell of the root is in its middle quarter, so it
+    /// can grow for a quarter of the root's width without being cut off.
+    fn is_padded(&mut self, id: NodeId) -> bool {
+        let inner = self.inner(id);
+        let quarter = self.inner(inner);
+        self.node(quarter).population == self.node(id).population
+    }
+
+    /// Shrink the root while all of its live cells fit in its middle half.
+    fn crop(&mut self) {
+        while self.level(self.root) > MIN_ROOT_LEVEL {
+            let inner = self.inner(self.root);
+            if self.node(inner).population != self.node(self.root).population {
+                break;
+            }
+            self.root = inner;
+        }
+    }
+
+    /// Drop every node no longer reachable from the root, along with all
+    /// memoised results.
+    pub fn collect_garbage(&mut self) {
+        let mut remap: HashMap<NodeId, NodeId> = HashMap::new();
+        let mut nodes = vec![self.nodes[DEAD as usize], self.nodes[ALIVE as usize]];
+        remap.insert(DEAD, DEAD);
+        remap.insert(ALIVE, ALIVE);
+
+        fn keep(
+            id: NodeId,
+            old: &[Node],
+            nodes: &mut Vec<Node>,
+            remap: &mut HashMap<NodeId, NodeId>,
+        ) -> NodeId {
+            if let Some(&new) = remap.get(&id) {
+                return new;
+            }
+
+            let mut node = old[id as usize];
+            for child in node.children.iter_mut() {
+                *child = keep(*child, old, nodes, remap);
+            }
+
+            let new = nodes.len() as NodeId;
+            nodes.push(node);
+            remap.insert(id, new);
+            new
+        }
+
+        self.root = keep(self.root, &self.nodes, &mut nodes, &mut remap);
+        for empty in self.empty.iter_mut() {
+            *empty = keep(*empty, &self.nodes, &mut nodes, &mut remap);
+        }
+
+        self.lookup = nodes
+            .iter()
+            .enumerate()
+            .skip(2)
+            .map(|(id, node)| (node.children, id as NodeId))
+            .collect();
+        self.nodes = nodes;
+        self.results.clear();
+    }
+}
+
+impl Default for HashLife {
+    fn default() -> HashLife {
+        HashLife::new()
+    }
+}
+
+// Public methods, exported to JavaScript.
+#[wasm_bindgen]
+impl HashLife {
+    pub fn new() -> HashLife {
+        let leaf = |level| Node {
+            children: [DEAD; 4],
+            level,
+            population: level as u64,
+        };
+
+        // Level 0 has the dead and alive cells, both with a level of 0.
+        let mut hashlife = HashLife {
+            nodes: vec![
+                leaf(0),
+                Node {
+                    population: 1,
+                    ..leaf(0)
+                },
+            ],
+            lookup: HashMap::new(),
+            results: HashMap::new(),
+            empty: vec![DEAD],
+            root: DEAD,
+            rule: Rule::default(),
+            generation: 0,
+            max_nodes: 1 << 22,
+        };
+        hashlife.root = hashlife.empty(MIN_ROOT_LEVEL);
+        hashlife
+    }
+
+    /// Set the rule the Universe evolves under, forgetting any memoised
+    /// results.
+    ///
+    /// Rules with more than two states, an extended neighbourhood or births
+    /// from empty neighbourhoods are rejected.
+    pub fn set_rule(&mut self, rule: &str) -> Result<(), RuleError> {
+        let parsed = Rule::parse(rule)?;
+        if parsed.states() > 2 || parsed.radius() > 1 || parsed.births_from_nothing() {
+            return Err(RuleError::Unsupported(rule.to_string()));
+        }
+
+        self.rule = parsed;
+        self.results.clear();
+        Ok(())
+    }
+
+    /// The current rule in `B/S` notation.
+    pub fn rule(&self) -> String {
+        self.rule.to_string()
+    }
+
+    /// Advance by `2^k` generations in one step.
+    ///
+    /// # Panics
+    ///
+    /// If `k` is larger than `MAX_STEP`, or the pattern spreads too far
+    /// from the origin for its rows and columns to fit in an `i64`.
+    pub fn step_pow2(&mut self, k: u8) {
+        assert!(
+            k <= MAX_STEP,
+            "steps are limited to 2^{} generations",
+            MAX_STEP
+        );
+        while self.level(self.root) < k + 3 || !self.is_padded(self.root) {
+            assert!(
+                self.level(self.root) < MAX_ROOT_LEVEL,
+                "the pattern has spread too far to step"
+            );
+            self.root = self.centre(self.root);
+        }
+
+        self.root = self.successor(self.root, k);
+        self.generation = self.generation.saturating_add(1 << k);
+        self.crop();
+
+        if self.nodes.len() > self.max_nodes {
+            self.collect_garbage();
+        }
+    }
+
+    /// Advance by `n` generations, as a series of power of two steps no
+    /// larger than `2^MAX_STEP`.
+    pub fn advance(&mut self, n: u64) {
+        for k in (0..64).filter(|k| n & (1 << k) != 0) {
+            let step = k.min(MAX_STEP);
+            for _ in 0..1u64 << (k - step) {
+                self.step_pow2(step);
+            }
+        }
+    }
+
+    /// Read a pattern in Golly's macrocell format. Only two-state rules
+    /// that `set_rule` accepts can be read.
+    pub fn from_macrocell(text: &str) -> Result<HashLife, PatternError> {
+        macrocell::parse_hashlife(text)
+    }
+
+    /// Write the pattern in Golly's macrocell format, which stays compact
+    /// however large a repetitive pattern grows.
+    pub fn to_macrocell(&self) -> String {
+        macrocell::write_hashlife(self)
+    }
+
+    /// Set how many nodes may be cached before garbage is collected after a
+    /// step.
+    pub fn set_max_nodes(&mut self, max_nodes: usize) {
+        self.max_nodes = max_nodes;
+    }
+
+    /// The number of nodes currently cached.
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Toggles the state of a cell
+    pub fn toggle(&mut self, row: i64, col: i64) {
+        let alive = self.is_alive(row, col);
+        self.set_cell(row, col, !alive);
+    }
+
+    pub fn is_alive(&self, row: i64, col: i64) -> bool {
+        let half = self.half_width(self.root);
+        if !(-half..half).contains(&row) || !(-half..half).contains(&col) {
+            return false;
+        }
+        self.get_in(self.root, row + half, col + half)
+    }
+
+    pub fn clear(&mut self) {
+        self.root = self.empty(MIN_ROOT_LEVEL);
+        self.generation = 0;
+    }
+
+    /// The number of live cells.
+    pub fn population(&self) -> u64 {
+        self.node(self.root).population
+    }
+
+    /// How many generations the Universe has been advanced.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// The smallest rectangle containing every live cell, or nothing if
+    /// every cell is dead.
+    pub fn bounding_box(&self) -> Option<BoundingBox> {
+        let half = self.half_width(self.root);
+        let extent = |column, last| {
+            let mut memo = HashMap::new();
+            self.extent(self.root, column, last, &mut memo)
+                .map(|extent| extent - half)
+        };
+
+        Some(BoundingBox {
+            top: extent(false, false)?,
+            left: extent(true, false)?,
+            bottom: extent(false, true)?,
+            right: extent(true, true)?,
+        })
+    }
+}
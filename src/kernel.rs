@@ -0,0 +1,152 @@
+use std::ops::{BitAnd, BitOr, BitXor, Not};
+
+use crate::rule::Rule;
+
+/// Steps 64 cells of a two-state rule on the immediate neighbourhood at a
+/// time, or a multiple of 64 with wider `Lanes`.
+///
+/// Each word holds one cell per bit, and the eight neighbour words hold the
+/// matching neighbour of every cell, clockwise from north to follow the
+/// neighbourhood masks used by `Rule`. Outer-totalistic rules count the
+/// neighbours of all 64 cells at once with bit-sliced adders, other rules
+/// fall back to a table lookup per cell.
+pub(crate) struct Kernel<'a> {
+    rule: &'a Rule,
+    counts: Option<(u16, u16)>,
+}
+
+/// A group of 64 bit words the kernel can step together with bitwise
+/// operations.
+pub(crate) trait Lanes:
+    Copy + BitAnd<Output = Self> + BitOr<Output = Self> + BitXor<Output = Self> + Not<Output = Self>
+{
+    /// How many words are stepped together.
+    const WORDS: usize;
+
+    fn from_fn(word: impl FnMut(usize) -> u64) -> Self;
+
+    fn word(self, lane: usize) -> u64;
+
+    fn zero() -> Self {
+        Self::from_fn(|_| 0)
+    }
+}
+
+impl Lanes for u64 {
+    const WORDS: usize = 1;
+
+    fn from_fn(mut word: impl FnMut(usize) -> u64) -> u64 {
+        word(0)
+    }
+
+    fn word(self, _: usize) -> u64 {
+        self
+    }
+}
+
+/// The lanes `Universe` steps with, 128 bit vectors with the `simd`
+/// feature and single words without.
+#[cfg(feature = "simd")]
+pub(crate) type Word = crate::simd::Wide;
+#[cfg(not(feature = "simd"))]
+pub(crate) type Word = u64;
+
+/// Add three one-bit numbers per lane, giving the sum and carry bits.
+fn full_add<L: Lanes>(a: L, b: L, c: L) -> (L, L) {
+    let partial = a ^ b;
+    (partial ^ c, (a & b) | (partial & c))
+}
+
+fn half_add<L: Lanes>(a: L, b: L) -> (L, L) {
+    (a ^ b, a & b)
+}
+
+impl<'a> Kernel<'a> {
+    pub fn new(rule: &'a Rule) -> Kernel<'a> {
+        Kernel {
+            rule,
+            counts: rule.count_masks(),
+        }
+    }
+
+    pub fn next_word(&self, neighbours: &[u64; 8], alive: u64) -> u64 {
+        self.next_lanes(neighbours, alive)
+    }
+
+    pub fn next_lanes<L: Lanes>(&self, neighbours: &[L; 8], alive: L) -> L {
+        match self.counts {
+            Some((birth, survival)) => Self::next_lanes_counted(neighbours, alive, birth, survival),
+            None => L::from_fn(|lane| {
+                let neighbours = neighbours.map(|neighbour| neighbour.word(lane));
+                self.next_word_per_cell(&neighbours, alive.word(lane))
+            }),
+        }
+    }
+
+    fn next_lanes_counted<L: Lanes>(neighbours: &[L; 8], alive: L, birth: u16, survival: u16) -> L {
+        let [n0, n1, n2, n3, n4, n5, n6, n7] = *neighbours;
+
+        // Sum the eight neighbours into a four bit count per lane.
+        let (ones_a, twos_a) = full_add(n0, n1, n2);
+        let (ones_b, twos_b) = full_add(n3, n4, n5);
+        let (ones_c, twos_c) = half_add(n6, n7);
+        let (bit0, twos_d) = full_add(ones_a, ones_b, ones_c);
+        let (twos, fours_a) = full_add(twos_a, twos_b, twos_c);
+        let (bit1, fours_b) = half_add(twos, twos_d);
+        let (bit2, bit3) = half_add(fours_a, fours_b);
+
+        let matches = |count: u16| {
+            let bit = |plane: L, set: u16| if count & set != 0 { plane } else { !plane };
+            bit(bit0, 1) & bit(bit1, 2) & bit(bit2, 4) & bit(bit3, 8)
+        };
+        let any = |mask: u16| {
+            (0..=8)
+                .filter(|count| mask & (1 << count) != 0)
+                .fold(L::zero(), |word, count| word | matches(count))
+        };
+
+        (alive & any(survival)) | (!alive & any(birth))
+    }
+
+    fn next_word_per_cell(&self, neighbours: &[u64; 8], alive: u64) -> u64 {
+        (0..64).fold(0, |word, bit| {
+            let neighbourhood = neighbours
+                .iter()
+                .enumerate()
+                .fold(0u8, |mask, (n, &neighbour)| {
+                    mask | (((neighbour >> bit) & 1) as u8) << n
+                });
+            let next = self.rule.next_state((alive >> bit) & 1 != 0, neighbourhood);
+            word | (next as u64) << bit
+        })
+    }
+}
+
+/// Read up to 64 bits starting at any bit of a slice of 32 bit blocks, with
+/// bit `start` ending up in bit 0.
+pub(crate) fn read_bits(blocks: &[u32], start: usize, len: usize) -> u64 {
+    let mut bits = 0;
+    let mut read = 0;
+    while read < len {
+        let (block, shift) = ((start + read) / 32, (start + read) % 32);
+        let take = (32 - shift).min(len - read);
+        let chunk = (blocks[block] >> shift) as u64 & ((1 << take) - 1);
+        bits |= chunk << read;
+        read += take;
+    }
+    bits
+}
+
+/// Write the low `len` bits of a word into a slice of 32 bit blocks from any
+/// bit onwards, leaving the surrounding bits alone.
+pub(crate) fn write_bits(blocks: &mut [u32], start: usize, len: usize, bits: u64) {
+    let mut written = 0;
+    while written < len {
+        let (block, shift) = ((start + written) / 32, (start + written) % 32);
+        let take = (32 - shift).min(len - written);
+        let mask = (((1u64 << take) - 1) as u32) << shift;
+        let chunk = ((bits >> written) as u32) << shift;
+        blocks[block] = (blocks[block] & !mask) | (chunk & mask);
+        written += take;
+    }
+}
@@ -1,12 +1,63 @@
 #[macro_use]
 mod utils;
-
-extern crate js_sys;
-extern crate web_sys;
+mod apgcode;
+mod formats;
+mod hashlife;
+mod kernel;
+mod platform;
+mod prng;
+mod render;
+mod resize;
+mod rule;
+mod rule_file;
+#[cfg(feature = "serde")]
+mod serde_support;
+#[cfg(any(feature = "simd", test))]
+mod simd;
+mod snapshot;
+mod soup;
+mod sparse;
+mod topology;
 
 use fixedbitset::FixedBitSet;
+use kernel::{Kernel, Lanes, Word};
+use platform::DefaultRandom;
 use wasm_bindgen::prelude::*;
-use web_sys::console;
+
+pub use apgcode::{decode_apgcode, encode_apgcode, ApgcodeError};
+pub use formats::{Format, Pattern, PatternError};
+pub use hashlife::{DoesNotFit, HashLife, MAX_STEP};
+#[cfg(target_arch = "wasm32")]
+pub use platform::{BrowserRandom, BrowserTiming};
+pub use platform::{NoTiming, Random, Timing};
+#[cfg(not(target_arch = "wasm32"))]
+pub use platform::{SystemRandom, SystemTiming};
+pub use prng::Xoshiro128;
+pub use render::{RenderError, RenderOptions, Viewport, ALIVE_COLOR, DEAD_COLOR, GRID_COLOR};
+pub use resize::{Anchor, Resized};
+pub use rule::{LargerThanLife, Rule, RuleError, Shape};
+pub use rule_file::{GollyRule, RuleFileError};
+pub use snapshot::SnapshotError;
+pub use soup::{SoupError, Symmetry};
+pub use sparse::{BoundingBox, SparseUniverse, TILE_SIZE};
+pub use topology::{Bounds, Topology};
+
+/// The width and height in cells of the tiles whose changes are tracked
+/// between generations.
+const CHANGE_TILE_SIZE: usize = 64;
+
+/// Offsets of the eight neighbours of a cell, clockwise from north to match
+/// the neighbourhood masks used by `Rule`.
+const NEIGHBOURS: [(i64, i64); 8] = [
+    (-1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+    (1, 0),
+    (1, -1),
+    (0, -1),
+    (-1, -1),
+];
 
 // When the `wee_alloc` feature is enabled, use `wee_alloc` as the global
 // allocator.
@@ -14,20 +65,30 @@ use web_sys::console;
 #[global_allocator]
 static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 
-pub struct Timer<'a> {
+/// Reports how long it lived for when dropped, through `console.time` in
+/// the browser. Natively nothing is reported unless a `Timing` such as
+/// `SystemTiming` is given to `with_timing`.
+pub struct Timer<'a, T: Timing = platform::DefaultTiming> {
     name: &'a str,
+    timing: T,
 }
 
 impl<'a> Timer<'a> {
     pub fn new(name: &'a str) -> Timer<'a> {
-        console::time_with_label(name);
-        Timer { name }
+        Timer::with_timing(name, Default::default())
     }
 }
 
-impl<'a> Drop for Timer<'a> {
+impl<'a, T: Timing> Timer<'a, T> {
+    pub fn with_timing(name: &'a str, mut timing: T) -> Timer<'a, T> {
+        timing.start(name);
+        Timer { name, timing }
+    }
+}
+
+impl<'a, T: Timing> Drop for Timer<'a, T> {
     fn drop(&mut self) {
-        console::time_end_with_label(self.name);
+        self.timing.end(self.name);
     }
 }
 
@@ -36,9 +97,56 @@ pub struct Universe {
     width: u32,
     height: u32,
     cells: FixedBitSet,
+    /// One byte per cell for rules with more than two states. While the rule
+    /// only has two states `cells` is authoritative and this is only filled
+    /// in on request from JavaScript.
+    states: Vec<u8>,
+    rule: Rule,
+    topology: Topology,
+    /// Which tiles changed since the last generation was computed, in rows
+    /// of `CHANGE_TILE_SIZE` square tiles.
+    changed: Vec<bool>,
+    /// How many times the Universe has ticked since it was last cleared or
+    /// reset.
+    generation: u64,
+    /// A rule loaded from a Golly `.rule` file, used in place of `rule`.
+    rule_file: Option<GollyRule>,
+    /// The chance of each cell being alive when the Universe is filled with
+    /// random cells.
+    density: f64,
 }
 
 impl Universe {
+    /// Create a `width` by `height` Universe with every cell dead.
+    ///
+    /// # Panics
+    ///
+    /// If the Universe would hold more cells than `fits` allows.
+    pub fn empty(width: u32, height: u32) -> Universe {
+        utils::set_panic_hook();
+        assert!(
+            Universe::fits(width, height),
+            "a {}x{} Universe has too many cells",
+            width,
+            height
+        );
+
+        let mut universe = Universe {
+            width,
+            height,
+            cells: FixedBitSet::with_capacity(width as usize * height as usize),
+            states: Vec::new(),
+            rule: Rule::default(),
+            topology: Topology::default(),
+            changed: Vec::new(),
+            generation: 0,
+            rule_file: None,
+            density: 0.5,
+        };
+        universe.mark_all_changed();
+        universe
+    }
+
     /// Get the dead and alive values of the entire Universe.
     pub fn get_cells(&self) -> &FixedBitSet {
         &self.cells
@@ -46,41 +154,561 @@ impl Universe {
 
     /// Set cells to be alive in a universe by passing the row and column
     /// of each cell as an array.
+    ///
+    /// Cells beyond the edges are wrapped according to the topology, or
+    /// dropped if they fall off a dead edge.
     pub fn set_cells(&mut self, cells: &[(u32, u32)]) {
         for (row, col) in cells.iter().cloned() {
+            self.set_state(row as i64, col as i64, 1);
+        }
+    }
+
+    /// Set cells to be alive at offsets from the given row and column,
+    /// wrapping them according to the topology.
+    pub fn stamp(&mut self, row: u32, col: u32, offsets: &[(i64, i64)]) {
+        for (delta_row, delta_col) in offsets.iter().cloned() {
+            self.set_state(row as i64 + delta_row, col as i64 + delta_col, 1);
+        }
+    }
+
+    /// Every live cell joined to the given one through live neighbours.
+    /// Cells reached across a wrapped edge keep counting past it, so the
+    /// object comes out in one piece.
+    fn object_at(&self, row: u32, col: u32) -> Vec<(i64, i64)> {
+        let mut seen = FixedBitSet::with_capacity(self.cells.len());
+        let mut object = Vec::new();
+        let mut stack = Vec::new();
+
+        let idx = self.get_index(row, col);
+        if self.cells[idx] {
+            seen.insert(idx);
+            stack.push((row as i64, col as i64));
+        }
+        while let Some((row, col)) = stack.pop() {
+            object.push((row, col));
+            for (delta_row, delta_col) in NEIGHBOURS.iter().cloned() {
+                let (row, col) = (row + delta_row, col + delta_col);
+                if let Some((wrapped_row, wrapped_col)) =
+                    self.topology.map(row, col, self.width, self.height)
+                {
+                    let idx = self.get_index(wrapped_row, wrapped_col);
+                    if self.cells[idx] && !seen.put(idx) {
+                        stack.push((row, col));
+                    }
+                }
+            }
+        }
+        object
+    }
+
+    /// Set the state of a cell that may lie beyond the edges, wrapping it
+    /// according to the topology. Dying states are only kept by
+    /// Generations rules, for other rules they leave the cell dead.
+    fn set_state(&mut self, row: i64, col: i64, state: u8) {
+        if let Some((row, col)) = self.topology.map(row, col, self.width, self.height) {
             let idx = self.get_index(row, col);
-            self.cells.set(idx, true);
+            if self.is_multi_state() {
+                self.states[idx] = state.min(self.state_count() - 1);
+            }
+            self.cells.set(idx, state == 1);
+            self.mark_changed(row, col);
         }
     }
 
-    fn get_index(&self, row: u32, col: u32) -> usize {
-        (row * self.width + col) as usize
+    /// Set the states of cells at offsets from the given row and column.
+    fn load_cells(&mut self, row: u32, col: u32, cells: &[(u32, u32, u8)]) {
+        for (delta_row, delta_col, state) in cells.iter().cloned() {
+            self.set_state(
+                row as i64 + delta_row as i64,
+                col as i64 + delta_col as i64,
+                state,
+            );
+        }
+    }
+
+    /// Get the state of a single cell. For two-state rules this is 0 or 1,
+    /// Generations rules also have dying states above 1.
+    pub fn get_state(&self, row: u32, col: u32) -> u8 {
+        let idx = self.get_index(row, col);
+        if self.is_multi_state() {
+            self.states[idx]
+        } else {
+            self.cells[idx] as u8
+        }
+    }
+
+    /// Get the rule the Universe is evolving under.
+    pub fn get_rule(&self) -> &Rule {
+        &self.rule
+    }
+
+    fn tile_columns(&self) -> usize {
+        (self.width as usize).div_ceil(CHANGE_TILE_SIZE)
+    }
+
+    fn tile_rows(&self) -> usize {
+        (self.height as usize).div_ceil(CHANGE_TILE_SIZE)
+    }
+
+    fn mark_changed(&mut self, row: u32, col: u32) {
+        let tile =
+            row as usize / CHANGE_TILE_SIZE * self.tile_columns() + col as usize / CHANGE_TILE_SIZE;
+        self.changed[tile] = true;
+    }
+
+    /// Flag every tile as changed, so the next generation is computed in
+    /// full.
+    fn mark_all_changed(&mut self) {
+        self.changed = vec![true; self.tile_rows() * self.tile_columns()];
+    }
+
+    /// The tiles that could change next generation: those that changed
+    /// last generation and the tiles around them.
+    ///
+    /// Cells on the edges may neighbour cells on any other edge depending
+    /// on the topology, so a change in any edge tile wakes all of them.
+    fn active_tiles(&self) -> Vec<bool> {
+        let (rows, columns) = (self.tile_rows() as i64, self.tile_columns() as i64);
+        let on_edge =
+            |row: i64, col: i64| row == 0 || col == 0 || row == rows - 1 || col == columns - 1;
+        let mut active = vec![false; self.changed.len()];
+        let mut wake_edges = false;
+
+        for (tile, _) in self
+            .changed
+            .iter()
+            .enumerate()
+            .filter(|(_, &changed)| changed)
+        {
+            let (row, col) = (tile as i64 / columns, tile as i64 % columns);
+            for delta_row in -1..=1 {
+                for delta_col in -1..=1 {
+                    let (row, col) = (row + delta_row, col + delta_col);
+                    if (0..rows).contains(&row) && (0..columns).contains(&col) {
+                        active[(row * columns + col) as usize] = true;
+                    }
+                }
+            }
+            wake_edges |= on_edge(row, col) && self.topology != Topology::Plane;
+        }
+
+        if wake_edges {
+            for row in 0..rows {
+                for col in (0..columns).filter(|&col| on_edge(row, col)) {
+                    active[(row * columns + col) as usize] = true;
+                }
+            }
+        }
+
+        active
+    }
+
+    /// Flag the tiles whose cells differ from an earlier generation.
+    fn record_changes(&mut self, before: &FixedBitSet) {
+        let (width, columns) = (self.width as usize, self.tile_columns());
+        let mut changed = vec![false; self.changed.len()];
+
+        for row in 0..self.height as usize {
+            for chunk in 0..columns {
+                let (start, len) = (
+                    row * width + chunk * CHANGE_TILE_SIZE,
+                    (width - chunk * CHANGE_TILE_SIZE).min(CHANGE_TILE_SIZE),
+                );
+                if kernel::read_bits(before.as_slice(), start, len)
+                    != kernel::read_bits(self.cells.as_slice(), start, len)
+                {
+                    changed[row / CHANGE_TILE_SIZE * columns + chunk] = true;
+                }
+            }
+        }
+
+        self.changed = changed;
+    }
+
+    fn is_multi_state(&self) -> bool {
+        self.rule_file.is_some() || self.rule.states() > 2
     }
 
-    fn live_neighbour_count(&self, row: u32, col: u32) -> u8 {
-        let mut count = 0;
-        for delta_row in [self.height - 1, 0, 1].iter().cloned() {
-            for delta_col in [self.width - 1, 0, 1].iter().cloned() {
-                if delta_row == 0 && delta_col == 0 {
+    /// The name of the rule without any topology suffix.
+    pub(crate) fn rule_name(&self) -> String {
+        match &self.rule_file {
+            Some(rule_file) => rule_file.name().to_string(),
+            None => self.rule.to_string(),
+        }
+    }
+
+    /// The loaded `.rule` file, if the Universe is running one.
+    pub fn get_rule_file(&self) -> Option<&GollyRule> {
+        self.rule_file.as_ref()
+    }
+
+    /// Rebuild the per-cell states from the live cells, dropping any dying
+    /// cells.
+    fn refresh_states(&mut self) {
+        let cells = &self.cells;
+        self.states = (0..cells.len()).map(|idx| cells[idx] as u8).collect();
+    }
+
+    /// Count the live cells in every cell's Larger than Life neighbourhood.
+    ///
+    /// The board is padded by the radius on every side and turned into a
+    /// summed-area table, so each row of a neighbourhood costs one lookup
+    /// and a Moore neighbourhood costs a single rectangle lookup.
+    fn larger_than_life_counts(&self, ltl: &LargerThanLife) -> Vec<u32> {
+        let radius = ltl.radius() as usize;
+        let (width, height) = (self.width as usize, self.height as usize);
+        let padded_width = width + 2 * radius;
+        let padded_height = height + 2 * radius;
+
+        // sums[y][x] holds the live cells above row y and left of column x.
+        let stride = padded_width + 1;
+        let mut sums = vec![0u32; (padded_height + 1) * stride];
+        for y in 0..padded_height {
+            let mut row_sum = 0;
+            for x in 0..padded_width {
+                let idx = self.wrapped_index(y as i64 - radius as i64, x as i64 - radius as i64);
+                row_sum += idx.map_or(0, |idx| self.cells[idx] as u32);
+                sums[(y + 1) * stride + x + 1] = sums[y * stride + x + 1] + row_sum;
+            }
+        }
+
+        let area = |top: usize, left: usize, bottom: usize, right: usize| {
+            sums[bottom * stride + right] + sums[top * stride + left]
+                - sums[top * stride + right]
+                - sums[bottom * stride + left]
+        };
+
+        let half_widths: Vec<usize> = (0..=2 * radius)
+            .map(|dy| ltl.half_width((dy as i64 - radius as i64).unsigned_abs() as u32) as usize)
+            .collect();
+
+        let mut counts = Vec::with_capacity(width * height);
+        for row in 0..height {
+            for col in 0..width {
+                let mut count = match ltl.shape() {
+                    Shape::Moore => area(row, col, row + 2 * radius + 1, col + 2 * radius + 1),
+                    _ => half_widths
+                        .iter()
+                        .enumerate()
+                        .map(|(dy, &half_width)| {
+                            let left = col + radius - half_width;
+                            area(row + dy, left, row + dy + 1, left + 2 * half_width + 1)
+                        })
+                        .sum(),
+                };
+
+                if !ltl.includes_middle() {
+                    count -= self.cells[row * width + col] as u32;
+                }
+                counts.push(count);
+            }
+        }
+
+        counts
+    }
+
+    fn tick_larger_than_life(&mut self, ltl: &LargerThanLife) {
+        let counts = self.larger_than_life_counts(ltl);
+        let before = self.cells.clone();
+        let mut next = self.cells.clone();
+
+        if self.is_multi_state() {
+            for (idx, &count) in counts.iter().enumerate() {
+                let state = self.states[idx];
+                self.states[idx] = self.rule.decay(state, ltl.next_state(state == 1, count));
+                next.set(idx, self.states[idx] == 1);
+            }
+        } else {
+            for (idx, &count) in counts.iter().enumerate() {
+                next.set(idx, ltl.next_state(self.cells[idx], count));
+            }
+        }
+
+        self.cells = next;
+        if self.is_multi_state() {
+            self.mark_all_changed();
+        } else {
+            self.record_changes(&before);
+        }
+    }
+
+    /// Step a two-state rule on the immediate neighbourhood 64 cells at a
+    /// time, or 128 with the `simd` feature.
+    ///
+    /// Every row is copied into words with one extra cell either side, and
+    /// extra rows above and below, filled from across the edges. The kernel
+    /// then works on whole words without having to consult the topology.
+    fn tick_words(&mut self) {
+        let (width, height) = (self.width as usize, self.height as usize);
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        // Padded bit `col + 1` of padded row `row + 1` holds cell (row, col),
+        // with spare words so a row can always be read a full set of lanes
+        // at a time.
+        let chunks = width.div_ceil(64);
+        let stride = chunks + Word::WORDS;
+        let mut rows = vec![0u64; (height + 2) * stride];
+
+        let blocks = self.cells.as_slice();
+        for row in 0..height {
+            let padded = &mut rows[(row + 1) * stride..(row + 2) * stride];
+            for chunk in 0..chunks {
+                let bits = kernel::read_bits(
+                    blocks,
+                    row * width + chunk * 64,
+                    (width - chunk * 64).min(64),
+                );
+                padded[chunk] |= bits << 1;
+                padded[chunk + 1] |= bits >> 63;
+            }
+        }
+
+        let edges = (-1..=height as i64)
+            .flat_map(|row| vec![(row, -1), (row, width as i64)])
+            .chain((0..width as i64).flat_map(|col| vec![(-1, col), (height as i64, col)]));
+        for (row, col) in edges {
+            if self
+                .wrapped_index(row, col)
+                .is_some_and(|idx| self.cells[idx])
+            {
+                let bit = (row + 1) as usize * stride * 64 + (col + 1) as usize;
+                rows[bit / 64] |= 1 << (bit % 64);
+            }
+        }
+
+        // 64 padded bits of a padded row, starting at any bit.
+        let word = |row: usize, offset: usize| {
+            let words = &rows[row * stride..];
+            let (idx, shift) = (offset / 64, offset % 64);
+            if shift == 0 {
+                words[idx]
+            } else {
+                (words[idx] >> shift) | (words[idx + 1] << (64 - shift))
+            }
+        };
+        // Consecutive runs of 64 padded bits, one per lane.
+        let lanes = |row: usize, offset: usize| Word::from_fn(|lane| word(row, offset + lane * 64));
+
+        // Each chunk of 64 cells in a row lies within one tile, so chunks in
+        // quiet tiles are left as they are.
+        let active = self.active_tiles();
+        let mut changed = vec![false; active.len()];
+        let tile = |row: usize, chunk: usize| row / CHANGE_TILE_SIZE * chunks + chunk;
+
+        let kernel = Kernel::new(&self.rule);
+        let mut next = self.cells.clone();
+        for row in 0..height {
+            let (above, middle, below) = (row, row + 1, row + 2);
+            for chunk in (0..chunks).step_by(Word::WORDS) {
+                let group = chunk..(chunk + Word::WORDS).min(chunks);
+                if !group.clone().any(|chunk| active[tile(row, chunk)]) {
                     continue;
                 }
 
-                let neighbour_row = (row + delta_row) % self.height;
-                let neighbour_col = (col + delta_col) % self.width;
-                let idx = self.get_index(neighbour_row, neighbour_col);
-                count += self.cells[idx] as u8;
+                let col = chunk * 64;
+                let neighbours = [
+                    lanes(above, col + 1),
+                    lanes(above, col + 2),
+                    lanes(middle, col + 2),
+                    lanes(below, col + 2),
+                    lanes(below, col + 1),
+                    lanes(below, col),
+                    lanes(middle, col),
+                    lanes(above, col),
+                ];
+                let bits = kernel.next_lanes(&neighbours, lanes(middle, col + 1));
+
+                for (lane, chunk) in group.enumerate() {
+                    let (start, len) = (row * width + chunk * 64, (width - chunk * 64).min(64));
+                    if kernel::read_bits(next.as_slice(), start, len)
+                        != bits.word(lane) & (u64::MAX >> (64 - len))
+                    {
+                        kernel::write_bits(next.as_mut_slice(), start, len, bits.word(lane));
+                        changed[tile(row, chunk)] = true;
+                    }
+                }
             }
         }
 
-        count
+        self.cells = next;
+        self.changed = changed;
+    }
+
+    /// The states of a cell and its neighbours, with cells beyond a dead
+    /// edge counted as state 0.
+    fn state_neighbourhood(&self, row: u32, col: u32) -> rule_file::Neighbourhood {
+        let mut cells = [self.states[self.get_index(row, col)]; 9];
+        for (cell, &(delta_row, delta_col)) in cells[1..].iter_mut().zip(NEIGHBOURS.iter()) {
+            *cell = self
+                .wrapped_index(row as i64 + delta_row, col as i64 + delta_col)
+                .map_or(0, |idx| self.states[idx]);
+        }
+        cells
+    }
+
+    fn tick_rule_file(&mut self) {
+        let neighbourhoods: Vec<_> = (0..self.height)
+            .flat_map(|row| (0..self.width).map(move |col| (row, col)))
+            .map(|(row, col)| self.state_neighbourhood(row, col))
+            .collect();
+        let rule_file = self
+            .rule_file
+            .as_mut()
+            .expect("only ticked with a rule file");
+        let next_states: Vec<u8> = neighbourhoods
+            .iter()
+            .map(|neighbourhood| rule_file.next_state(neighbourhood))
+            .collect();
+
+        self.changed = vec![false; self.changed.len()];
+        for row in 0..self.height {
+            for col in 0..self.width {
+                let idx = self.get_index(row, col);
+                if next_states[idx] != self.states[idx] {
+                    self.cells.set(idx, next_states[idx] == 1);
+                    self.mark_changed(row, col);
+                }
+            }
+        }
+        self.states = next_states;
     }
 
-    fn seed(bits: u32) -> Vec<u32> {
-        let factor = 100_000_000_000_000_000.0;
+    fn tick_generations(&mut self) {
+        let mut next = self.cells.clone();
+        let mut next_states = self.states.clone();
 
-        (0..(bits/32)).into_iter().map(|_|
-            ((js_sys::Math::trunc(js_sys::Math::random() * factor) as u64) >> 32) as u32
-        ).collect()
+        for row in 0..self.height {
+            for col in 0..self.width {
+                let idx = self.get_index(row, col);
+                let neighbourhood = self.live_neighbourhood(row, col);
+                let state = self
+                    .rule
+                    .next_generations_state(self.states[idx], neighbourhood);
+
+                next_states[idx] = state;
+                next.set(idx, state == 1);
+            }
+        }
+
+        self.cells = next;
+        self.states = next_states;
+        // Dying cells age every generation, so changes aren't tracked.
+        self.mark_all_changed();
+    }
+
+    fn get_index(&self, row: u32, col: u32) -> usize {
+        (row * self.width + col) as usize
+    }
+
+    /// The index of a cell that may lie beyond the edges, following the
+    /// topology.
+    fn wrapped_index(&self, row: i64, col: i64) -> Option<usize> {
+        self.topology
+            .map(row, col, self.width, self.height)
+            .map(|(row, col)| self.get_index(row, col))
+    }
+
+    /// The live neighbours of a cell as a bitmask, clockwise from north in
+    /// bit 0 to north-west in bit 7.
+    fn live_neighbourhood(&self, row: u32, col: u32) -> u8 {
+        NEIGHBOURS
+            .iter()
+            .enumerate()
+            .fold(0, |mask, (bit, &(delta_row, delta_col))| {
+                let alive = self
+                    .wrapped_index(row as i64 + delta_row, col as i64 + delta_col)
+                    .is_some_and(|idx| self.cells[idx]);
+                mask | (alive as u8) << bit
+            })
+    }
+
+    /// Whether a `width` by `height` Universe can be made. Cells are indexed
+    /// with a `u32`, so there can be no more than `u32::MAX` of them.
+    pub fn fits(width: u32, height: u32) -> bool {
+        width as u64 * height as u64 <= u32::MAX as u64
+    }
+
+    /// The number of cells in the Universe.
+    fn len(&self) -> usize {
+        self.width as usize * self.height as usize
+    }
+
+    /// Random blocks for the given number of cells, each alive with the
+    /// given chance.
+    ///
+    /// The last block is only partly used unless the number of cells is a
+    /// multiple of 32, and the bits past the last cell are left clear.
+    fn seed(bits: usize, density: f64, random: &mut impl Random) -> Vec<u32> {
+        (0..bits.div_ceil(32))
+            .map(|block| {
+                let len = (bits - block * 32).min(32);
+                let mask = u32::MAX >> (32 - len);
+                if density == 0.5 {
+                    return random.next_u32() & mask;
+                }
+                (0..len).fold(0, |block, bit| {
+                    block | (random.chance(density) as u32) << bit
+                })
+            })
+            .collect()
+    }
+
+    /// Fill a rectangle with a random soup with the given symmetry, using
+    /// cells from the given source alive with the chance set by
+    /// `set_density`. Cells beyond the edges are wrapped according to the
+    /// topology.
+    ///
+    /// The soup is the largest that fits the rectangle, see
+    /// `Symmetry::fit`, and is returned.
+    pub fn soup_with(
+        &mut self,
+        row: u32,
+        col: u32,
+        width: u32,
+        height: u32,
+        symmetry: Symmetry,
+        random: &mut impl Random,
+    ) -> Viewport {
+        let (width, height) = symmetry.fit(width, height);
+        let cells = soup::generate(width, height, symmetry, self.density, random);
+        for (idx, &alive) in cells.iter().enumerate() {
+            let (delta_row, delta_col) = (idx as u32 / width, idx as u32 % width);
+            self.set_state(
+                row as i64 + delta_row as i64,
+                col as i64 + delta_col as i64,
+                alive as u8,
+            );
+        }
+
+        Viewport {
+            row,
+            col,
+            width,
+            height,
+        }
+    }
+
+    /// Fill the Universe with random cells from the given source, alive
+    /// with the chance set by `set_density`, and start counting generations
+    /// again.
+    pub fn reset_with(&mut self, random: &mut impl Random) {
+        self.cells = FixedBitSet::with_capacity_and_blocks(
+            self.len(),
+            Self::seed(self.len(), self.density, random),
+        );
+        if self.is_multi_state() {
+            self.refresh_states();
+        }
+        self.generation = 0;
+        self.mark_all_changed();
+    }
+}
+
+impl Default for Universe {
+    fn default() -> Universe {
+        Universe::new()
     }
 }
 
@@ -89,102 +717,480 @@ impl Universe {
 impl Universe {
     /// Set the width of the Universe
     ///
-    /// Resets all cells to the dead state.
+    /// Resets all cells to the dead state. Panics if the new size doesn't
+    /// `fit`.
     pub fn set_width(&mut self, width: u32) {
+        assert!(
+            Universe::fits(width, self.height),
+            "a {}x{} Universe has too many cells",
+            width,
+            self.height
+        );
         self.width = width;
-        self.cells = FixedBitSet::with_capacity((self.width * self.height) as usize);
+        self.cells = FixedBitSet::with_capacity(self.len());
+        self.refresh_states();
+        self.mark_all_changed();
     }
 
     /// Set the height of the Universe
     ///
-    /// Resets all cells to the dead state.
+    /// Resets all cells to the dead state. Panics if the new size doesn't
+    /// `fit`.
     pub fn set_height(&mut self, height: u32) {
+        assert!(
+            Universe::fits(self.width, height),
+            "a {}x{} Universe has too many cells",
+            self.width,
+            height
+        );
         self.height = height;
-        self.cells = FixedBitSet::with_capacity((self.height * self.width) as usize);
+        self.cells = FixedBitSet::with_capacity(self.len());
+        self.refresh_states();
+        self.mark_all_changed();
+    }
+
+    /// Change the size of the Universe, keeping its cells in place relative
+    /// to the anchor. Cells that end up beyond the new edges are lost and
+    /// counted in the result.
+    ///
+    /// Panics if the new size doesn't `fit`.
+    pub fn resize(&mut self, width: u32, height: u32, anchor: Anchor) -> Resized {
+        assert!(
+            Universe::fits(width, height),
+            "a {}x{} Universe has too many cells",
+            width,
+            height
+        );
+        let (row_offset, col_offset) = anchor.offset((self.width, self.height), (width, height));
+        let cells: Vec<(u32, u32, u8)> = (0..self.height)
+            .flat_map(|row| (0..self.width).map(move |col| (row, col)))
+            .map(|(row, col)| (row, col, self.get_state(row, col)))
+            .filter(|&(_, _, state)| state != 0)
+            .collect();
+
+        self.width = width;
+        self.height = height;
+        self.cells = FixedBitSet::with_capacity(self.len());
+        self.refresh_states();
+
+        let mut cropped = 0;
+        for (row, col, state) in cells {
+            let (row, col) = (row as i64 + row_offset, col as i64 + col_offset);
+            if row < 0 || col < 0 || row >= height as i64 || col >= width as i64 {
+                cropped += 1;
+                continue;
+            }
+
+            let idx = self.get_index(row as u32, col as u32);
+            self.cells.set(idx, state == 1);
+            if self.is_multi_state() {
+                self.states[idx] = state;
+            }
+        }
+        self.mark_all_changed();
+
+        Resized {
+            row_offset,
+            col_offset,
+            cropped,
+        }
     }
 
     /// Toggles the state of a cell
     pub fn toggle(&mut self, row: u32, col: u32) {
         let idx = self.get_index(row, col);
-        self.cells.toggle(idx);
+        if self.is_multi_state() {
+            // Dying cells are cleared rather than brought back to life.
+            self.states[idx] = (self.states[idx] == 0) as u8;
+            self.cells.set(idx, self.states[idx] == 1);
+        } else {
+            self.cells.toggle(idx);
+        }
+        self.mark_changed(row, col);
     }
 
     /// Adds a glider centered on the specified cell
     pub fn glider(&mut self, row: u32, col: u32) {
-        let cells = [(row, col), (row, col + 1), (row - 1, col - 1), (row + 1, col), (row + 1, col - 1)];
+        let cells = [(0, 0), (0, 1), (-1, -1), (1, 0), (1, -1)];
 
-        self.set_cells(&cells);
+        self.stamp(row, col, &cells);
     }
 
     /// Adds a pulsar centered on the specified cell
     pub fn pulsar(&mut self, row: u32, col: u32) {
         let cells = [
-            (row - 1, col + 2), (row - 1, col + 3), (row - 1, col + 4),
-            (row - 1, col - 2), (row - 1, col - 3), (row - 1, col - 4),
-            (row - 2, col + 1), (row - 2, col + 6), (row - 2, col - 1), (row - 2, col - 6),
-            (row - 3, col + 1), (row - 3, col + 6), (row - 3, col - 1), (row - 3, col - 6),
-            (row - 4, col + 1), (row - 4, col + 6), (row - 4, col - 1), (row - 4, col - 6),
-            (row - 6, col + 2), (row - 6, col + 3), (row - 6, col + 4),
-            (row - 6, col - 2), (row - 6, col - 3), (row - 6, col - 4),
-            (row + 1, col + 2), (row + 1, col + 3), (row + 1, col + 4),
-            (row + 1, col - 2), (row + 1, col - 3), (row + 1, col - 4),
-            (row + 2, col + 1), (row + 2, col + 6), (row + 2, col - 1), (row + 2, col - 6),
-            (row + 3, col + 1), (row + 3, col + 6), (row + 3, col - 1), (row + 3, col - 6),
-            (row + 4, col + 1), (row + 4, col + 6), (row + 4, col - 1), (row + 4, col - 6),
-            (row + 6, col + 2), (row + 6, col + 3), (row + 6, col + 4),
-            (row + 6, col - 2), (row + 6, col - 3), (row + 6, col - 4)
+            (-1, 2),
+            (-1, 3),
+            (-1, 4),
+            (-1, -2),
+            (-1, -3),
+            (-1, -4),
+            (-2, 1),
+            (-2, 6),
+            (-2, -1),
+            (-2, -6),
+            (-3, 1),
+            (-3, 6),
+            (-3, -1),
+            (-3, -6),
+            (-4, 1),
+            (-4, 6),
+            (-4, -1),
+            (-4, -6),
+            (-6, 2),
+            (-6, 3),
+            (-6, 4),
+            (-6, -2),
+            (-6, -3),
+            (-6, -4),
+            (1, 2),
+            (1, 3),
+            (1, 4),
+            (1, -2),
+            (1, -3),
+            (1, -4),
+            (2, 1),
+            (2, 6),
+            (2, -1),
+            (2, -6),
+            (3, 1),
+            (3, 6),
+            (3, -1),
+            (3, -6),
+            (4, 1),
+            (4, 6),
+            (4, -1),
+            (4, -6),
+            (6, 2),
+            (6, 3),
+            (6, 4),
+            (6, -2),
+            (6, -3),
+            (6, -4),
         ];
 
-        self.set_cells(&cells);
+        self.stamp(row, col, &cells);
     }
 
-    pub fn tick(&mut self) {
-        let _timer = Timer::new("Universe::tick");
-        let mut next = self.cells.clone();
+    /// The apgcode of the object with a live cell at the given row and
+    /// column, made up of every live cell joined to it through live
+    /// neighbours.
+    pub fn apgcode_at(&self, row: u32, col: u32) -> Result<String, ApgcodeError> {
+        encode_apgcode(&self.object_at(row, col), &self.rule_name())
+    }
 
-        for row in 0..self.height {
-            for col in 0..self.width {
-                let idx = self.get_index(row, col);
-                let cell = self.cells[idx];
-                let live_neighbours = self.live_neighbour_count(row, col);
+    /// Adds the object named by an apgcode with its top left corner on the
+    /// specified cell.
+    pub fn stamp_apgcode(&mut self, row: u32, col: u32, code: &str) -> Result<(), ApgcodeError> {
+        let cells = decode_apgcode(code)?;
+        self.stamp(row, col, &cells);
+        Ok(())
+    }
 
-                next.set(idx, match (cell, live_neighbours) {
-                    (true, x) if x < 2 => false,
-                    (true, 2) | (true, 3) => true,
-                    (true, x) if x > 3 => false,
-                    (false, 3) => true,
-                    (otherwise, _) => otherwise
-                });
-            }
+    pub fn tick(&mut self) {
+        let _timer = Timer::new("Universe::tick");
+        self.generation += 1;
+        if self.rule_file.is_some() {
+            return self.tick_rule_file();
+        }
+        if let Some(ltl) = self.rule.larger_than_life().cloned() {
+            return self.tick_larger_than_life(&ltl);
+        }
+        if self.is_multi_state() {
+            return self.tick_generations();
         }
 
-        self.cells = next;
+        self.tick_words();
     }
 
     pub fn new() -> Universe {
-        utils::set_panic_hook();
+        let mut universe = Universe::empty(128, 128);
+        universe.reset();
+        universe
+    }
+
+    /// Create a `width` by `height` Universe filled with random cells from
+    /// a seed. The same seed always gives the same Universe.
+    pub fn with_seed(width: u32, height: u32, seed: u64) -> Universe {
+        let mut universe = Universe::empty(width, height);
+        universe.reset_with_seed(seed);
+        universe
+    }
 
-        let width: u32 = 128;
-        let height: u32 = 128;
-        let capacity = (width * height) as usize;
+    pub fn reset(&mut self) {
+        self.reset_with(&mut DefaultRandom::default());
+    }
 
-        let cells = FixedBitSet::with_capacity_and_blocks(capacity, Self::seed(width * height));
+    /// Fill the Universe with random cells from a seed, as `with_seed` does.
+    pub fn reset_with_seed(&mut self, seed: u64) {
+        self.reset_with(&mut Xoshiro128::new(seed));
+    }
 
-        Universe {
+    /// Fill a rectangle with a random soup from a seed, with the symmetry
+    /// enforced the way apgsearch does for a census. The same seed always
+    /// gives the same soup.
+    ///
+    /// A soup whose centre has to lie on a cell needs an odd width, and one
+    /// centred between cells an even width, so the rectangle may be
+    /// shortened by a cell. Rotations and diagonal reflections fill a
+    /// square. The rest of the Universe is left alone.
+    ///
+    /// Returns the rectangle the soup filled.
+    pub fn symmetric_soup(
+        &mut self,
+        row: u32,
+        col: u32,
+        width: u32,
+        height: u32,
+        symmetry: Symmetry,
+        seed: u64,
+    ) -> Viewport {
+        self.soup_with(
+            row,
+            col,
             width,
             height,
-            cells
+            symmetry,
+            &mut Xoshiro128::new(seed),
+        )
+    }
+
+    /// The chance of each cell being alive after a reset, from 0 to 1.
+    pub fn density(&self) -> f64 {
+        self.density
+    }
+
+    /// Set the chance of each cell being alive after a reset. It is
+    /// clamped to between 0 and 1, and defaults to a half.
+    pub fn set_density(&mut self, density: f64) {
+        self.density = if density.is_nan() {
+            0.5
+        } else {
+            density.clamp(0.0, 1.0)
+        };
+    }
+
+    /// Set the rule the Universe evolves under from a rulestring such as
+    /// `B36/S23`, `23/3`, the Generations rule `B2/S345/C4` or the Larger
+    /// than Life rule `R5,C0,M1,S34..58,B34..45,NM`.
+    ///
+    /// A Golly topology suffix such as `:P64,64` or `:K100*,80` changes the
+    /// topology, and if it has a size the Universe is resized and cleared.
+    /// Without a suffix the topology is left alone.
+    ///
+    /// Live cells are kept, any dying cells are cleared.
+    pub fn set_rule(&mut self, rule: &str) -> Result<(), RuleError> {
+        let (rule, suffix) = match rule.split_once(':') {
+            Some((rule, suffix)) => (rule, Some(suffix)),
+            None => (rule, None),
+        };
+        let bounds = suffix.map(Topology::parse).transpose()?;
+        if let Some(bounds) = bounds {
+            let width = bounds.width.unwrap_or(self.width);
+            let height = bounds.height.unwrap_or(self.height);
+            if !Universe::fits(width, height) {
+                return Err(RuleError::InvalidTopology(
+                    suffix.unwrap_or_default().to_string(),
+                ));
+            }
         }
+        self.rule = Rule::parse(rule)?;
+        self.rule_file = None;
+
+        if let Some(bounds) = bounds {
+            self.topology = bounds.topology;
+            let width = bounds.width.unwrap_or(self.width);
+            let height = bounds.height.unwrap_or(self.height);
+            if (width, height) != (self.width, self.height) {
+                self.width = width;
+                self.set_height(height);
+            }
+        }
+
+        if self.is_multi_state() {
+            self.refresh_states();
+        } else {
+            self.states = Vec::new();
+        }
+        self.mark_all_changed();
+        Ok(())
     }
 
-    pub fn reset(&mut self) {
-        let capacity = (self.width * self.height) as usize;
+    /// Run a rule from a Golly `.rule` file with a `@TABLE` or `@TREE`
+    /// section, such as WireWorld or Langton's Loops. `set_rule` switches
+    /// back to a rulestring.
+    ///
+    /// Live cells are kept, any other states are cleared.
+    pub fn load_rule_file(&mut self, text: &str) -> Result<(), RuleFileError> {
+        self.rule_file = Some(GollyRule::parse(text)?);
+        self.refresh_states();
+        self.mark_all_changed();
+        Ok(())
+    }
 
-        self.cells = FixedBitSet::with_capacity_and_blocks(capacity, Self::seed(self.width * self.height));
+    /// The number of states a cell can be in under the current rule.
+    pub fn state_count(&self) -> u8 {
+        match &self.rule_file {
+            Some(rule_file) => rule_file.states(),
+            None => self.rule.states(),
+        }
+    }
+
+    /// The current rule in `B/S` notation, or the name of a loaded `.rule`
+    /// file, with a topology suffix unless the Universe is a torus.
+    pub fn rule(&self) -> String {
+        format!(
+            "{}{}",
+            self.rule_name(),
+            self.topology.suffix(self.width, self.height)
+        )
+    }
+
+    /// Change how the edges of the Universe are joined. Cells are kept.
+    pub fn set_topology(&mut self, topology: Topology) {
+        self.topology = topology;
+        self.mark_all_changed();
+    }
+
+    pub fn topology(&self) -> Topology {
+        self.topology
     }
 
     pub fn clear(&mut self) {
         self.cells.clear();
+        self.states.iter_mut().for_each(|state| *state = 0);
+        self.generation = 0;
+        self.mark_all_changed();
+    }
+
+    /// How many times the Universe has ticked since it was last cleared or
+    /// reset.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// The width and height in cells of the tiles reported by
+    /// `changed_tiles`.
+    pub fn tile_size(&self) -> u32 {
+        CHANGE_TILE_SIZE as u32
+    }
+
+    /// The tiles that changed in the last generation, or were edited since,
+    /// numbered across each row of tiles and then down. A Universe `width`
+    /// cells wide has `ceil(width / tile_size)` tiles in a row.
+    pub fn changed_tiles(&self) -> Vec<u32> {
+        (0..self.changed.len() as u32)
+            .filter(|&tile| self.changed[tile as usize])
+            .collect()
+    }
+
+    /// Create a Universe from a pattern in Golly's run length encoded
+    /// format, sized to fit the pattern and with the rule from its header.
+    pub fn from_rle(rle: &str) -> Result<Universe, PatternError> {
+        Pattern::parse_as(rle, Format::Rle)?.to_universe()
+    }
+
+    /// Create a Universe from a pattern in RLE, plaintext, Life 1.05, Life
+    /// 1.06 or macrocell format, working out which from its contents.
+    ///
+    /// Fails if the pattern is too large to hold in a Universe.
+    pub fn from_pattern(text: &str) -> Result<Universe, PatternError> {
+        Pattern::parse(text)?.to_universe()
+    }
+
+    /// Add the live cells of an RLE pattern with its top left corner at
+    /// the given row and column, wrapping them according to the topology.
+    ///
+    /// The pattern's rule is ignored.
+    pub fn load_rle_at(&mut self, row: u32, col: u32, rle: &str) -> Result<(), PatternError> {
+        let pattern = Pattern::parse_as(rle, Format::Rle)?;
+        self.load_cells(row, col, &pattern.cells);
+        Ok(())
+    }
+
+    /// Add the live cells of a pattern in any supported format, as with
+    /// `load_rle_at`.
+    pub fn load_pattern_at(&mut self, row: u32, col: u32, text: &str) -> Result<(), PatternError> {
+        let pattern = Pattern::parse(text)?;
+        self.load_cells(row, col, &pattern.cells);
+        Ok(())
+    }
+
+    /// The whole Universe as an RLE pattern, with lines wrapped at 70
+    /// characters.
+    ///
+    /// With a `.rule` file loaded the header names the rule as Golly does,
+    /// so `from_rle` can't read the pattern back and Golly needs the file.
+    /// Use `serialize` to keep the whole rule.
+    pub fn to_rle(&self) -> String {
+        formats::rle::write(&Pattern::from_universe(self))
+    }
+
+    /// The whole Universe as a plaintext `.cells` pattern.
+    pub fn to_plaintext(&self) -> String {
+        formats::plaintext::write(&Pattern::from_universe(self))
+    }
+
+    /// The live cells of the Universe as a Life 1.05 pattern.
+    pub fn to_life105(&self) -> String {
+        formats::life105::write(&Pattern::from_universe(self))
+    }
+
+    /// The live cells of the Universe as a Life 1.06 pattern.
+    pub fn to_life106(&self) -> String {
+        formats::life106::write(&Pattern::from_universe(self))
+    }
+
+    /// The live cells of the Universe as a macrocell pattern.
+    ///
+    /// Fails for rules that a `HashLife` Universe can't run, such as
+    /// Generations rules.
+    pub fn to_macrocell(&self) -> Result<String, RuleError> {
+        formats::macrocell::write(&Pattern::from_universe(self))
+    }
+
+    /// Save the Universe, its rule, topology and generation count as a
+    /// compressed binary snapshot that `deserialize` can restore.
+    pub fn serialize(&self) -> Vec<u8> {
+        snapshot::serialize(self)
+    }
+
+    /// Restore a Universe saved by `serialize`.
+    pub fn deserialize(bytes: &[u8]) -> Result<Universe, SnapshotError> {
+        snapshot::deserialize(bytes)
+    }
+
+    /// The Universe as a snapshot in a compact string that is safe to use in
+    /// a URL, such as the fragment of a link to the board.
+    pub fn to_share_string(&self) -> String {
+        snapshot::to_share_string(self)
+    }
+
+    /// Restore a Universe from a string made by `to_share_string`.
+    pub fn from_share_string(text: &str) -> Result<Universe, SnapshotError> {
+        snapshot::from_share_string(text)
+    }
+
+    /// Draw the Universe as a PNG image.
+    pub fn to_png(&self, options: &RenderOptions) -> Result<Vec<u8>, RenderError> {
+        render::png(self, options)
+    }
+
+    /// Draw the Universe as an SVG image, which stays sharp at any scale.
+    pub fn to_svg(&self, options: &RenderOptions) -> String {
+        render::svg(self, options)
+    }
+
+    /// Record the given number of generations as an animated GIF, starting
+    /// from the current one, with a delay between frames in hundredths of a
+    /// second. The Universe is left at the last generation recorded.
+    pub fn record_gif(
+        &mut self,
+        options: &RenderOptions,
+        generations: u32,
+        delay: u16,
+    ) -> Result<Vec<u8>, RenderError> {
+        render::gif(self, options, generations, delay)
     }
 
     pub fn width(&self) -> u32 {
@@ -195,7 +1201,19 @@ impl Universe {
         self.height
     }
 
+    /// A pointer to the cells, one bit each in 32 bit blocks. There are
+    /// `width * height / 32` blocks rounded up, and the bits past the last
+    /// cell are always clear.
     pub fn cells(&self) -> *const u32 {
         self.cells.as_slice().as_ptr()
     }
+
+    /// A pointer to one byte per cell holding its state, for rendering
+    /// Generations rules.
+    pub fn states(&mut self) -> *const u8 {
+        if !self.is_multi_state() {
+            self.refresh_states();
+        }
+        self.states.as_ptr()
+    }
 }
@@ -0,0 +1,126 @@
+//! Randomness and timing, which come from the browser on wasm32 and from the
+//! standard library everywhere else, so the crate runs natively too.
+
+/// A source of random numbers for filling a Universe.
+pub trait Random {
+    /// The next 32 random bits.
+    fn next_u32(&mut self) -> u32;
+
+    /// True with the given probability, from 0 to 1.
+    fn chance(&mut self, probability: f64) -> bool {
+        // The threshold is out of 2^32 so that a probability of 1 is
+        // always true.
+        (self.next_u32() as u64) < (probability * 4_294_967_296.0) as u64
+    }
+}
+
+/// Reports how long labelled sections of code take.
+pub trait Timing {
+    fn start(&mut self, label: &str);
+    fn end(&mut self, label: &str);
+}
+
+/// `Math.random` from JavaScript.
+#[cfg(target_arch = "wasm32")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BrowserRandom;
+
+#[cfg(target_arch = "wasm32")]
+impl Random for BrowserRandom {
+    fn next_u32(&mut self) -> u32 {
+        // `Math.random` is in [0, 1), so this covers every u32.
+        (js_sys::Math::random() * 4_294_967_296.0) as u32
+    }
+}
+
+/// `console.time` and `console.timeEnd`, shown in the browser's developer
+/// tools.
+#[cfg(target_arch = "wasm32")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BrowserTiming;
+
+#[cfg(target_arch = "wasm32")]
+impl Timing for BrowserTiming {
+    fn start(&mut self, label: &str) {
+        web_sys::console::time_with_label(label);
+    }
+
+    fn end(&mut self, label: &str) {
+        web_sys::console::time_end_with_label(label);
+    }
+}
+
+/// Random numbers from hashing a counter with the randomly keyed hasher the
+/// standard library uses for `HashMap`.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone, Debug, Default)]
+pub struct SystemRandom {
+    state: std::collections::hash_map::RandomState,
+    counter: u64,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Random for SystemRandom {
+    fn next_u32(&mut self) -> u32 {
+        use std::hash::BuildHasher;
+
+        self.counter += 1;
+        (self.state.hash_one(self.counter) >> 32) as u32
+    }
+}
+
+/// Timing that reports nothing, the default off wasm32 so that the library
+/// doesn't print as it runs.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoTiming;
+
+impl Timing for NoTiming {
+    fn start(&mut self, _label: &str) {}
+
+    fn end(&mut self, _label: &str) {}
+}
+
+/// Elapsed times written to standard error in the same form as
+/// `console.timeEnd`, for use with `Timer::with_timing`.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone, Debug, Default)]
+pub struct SystemTiming {
+    started: Vec<(String, std::time::Instant)>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Timing for SystemTiming {
+    fn start(&mut self, label: &str) {
+        self.started
+            .push((label.to_string(), std::time::Instant::now()));
+    }
+
+    fn end(&mut self, label: &str) {
+        if let Some(idx) = self.started.iter().rposition(|(name, _)| name == label) {
+            let (_, started) = self.started.remove(idx);
+            eprintln!("{}: {}ms", label, started.elapsed().as_secs_f64() * 1000.0);
+        }
+    }
+}
+
+/// The random numbers used by `Universe::new` and `Universe::reset`.
+#[cfg(target_arch = "wasm32")]
+pub type DefaultRandom = BrowserRandom;
+#[cfg(not(target_arch = "wasm32"))]
+pub type DefaultRandom = SystemRandom;
+
+/// The timing used by `Timer`.
+#[cfg(target_arch = "wasm32")]
+pub type DefaultTiming = BrowserTiming;
+#[cfg(not(target_arch = "wasm32"))]
+pub type DefaultTiming = NoTiming;
+
+/// Print a line to the browser console, or to standard output natively.
+/// Used through the `log!` macro.
+#[allow(dead_code)]
+pub(crate) fn log(message: &str) {
+    #[cfg(target_arch = "wasm32")]
+    web_sys::console::log_1(&message.into());
+    #[cfg(not(target_arch = "wasm32"))]
+    println!("{}", message);
+}
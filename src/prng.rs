@@ -0,0 +1,55 @@
+//! A small seeded random number generator, so a soup can be regenerated
+//! from its seed on any platform.
+
+use crate::platform::Random;
+
+/// The xoshiro128++ generator by David Blackman and Sebastiano Vigna.
+///
+/// The same seed gives the same numbers everywhere, unlike the browser's
+/// `Math.random`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Xoshiro128 {
+    state: [u32; 4],
+}
+
+impl Xoshiro128 {
+    /// Create a generator from a seed, spreading it over the state with
+    /// SplitMix64 as the authors recommend.
+    pub fn new(seed: u64) -> Xoshiro128 {
+        let mut seed = seed;
+        let mut split_mix = || {
+            seed = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+            let mut z = seed;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+            z ^ (z >> 31)
+        };
+
+        let (first, second) = (split_mix(), split_mix());
+        Xoshiro128 {
+            state: [
+                first as u32,
+                (first >> 32) as u32,
+                second as u32,
+                (second >> 32) as u32,
+            ],
+        }
+    }
+}
+
+impl Random for Xoshiro128 {
+    fn next_u32(&mut self) -> u32 {
+        let s = &mut self.state;
+        let result = s[0].wrapping_add(s[3]).rotate_left(7).wrapping_add(s[0]);
+
+        let t = s[1] << 9;
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(11);
+
+        result
+    }
+}
@@ -0,0 +1,349 @@
+//! Drawing a Universe into PNG images, animated GIFs and SVG, laid out like
+//! the canvas in `www/index.js`.
+
+use std::fmt;
+
+use wasm_bindgen::prelude::*;
+
+use crate::Universe;
+
+/// The colours used by `www/index.js`, as `0xRRGGBB`.
+pub const GRID_COLOR: u32 = 0xCC_CC_CC;
+pub const DEAD_COLOR: u32 = 0xFF_FF_FF;
+pub const ALIVE_COLOR: u32 = 0x00_00_00;
+
+/// The widest or tallest image that can be drawn, the most a GIF can hold.
+const MAX_IMAGE_SIZE: u64 = u16::MAX as u64;
+
+/// How a Universe is drawn. Colours are given as `0xRRGGBB`.
+///
+/// The defaults match the canvas: five pixel cells with a one pixel grid
+/// between them, black live cells and white dead ones.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RenderOptions {
+    /// The width and height of a cell in pixels, not counting grid lines.
+    pub cell_size: u32,
+    /// Whether to draw one pixel lines around every cell.
+    pub grid: bool,
+    pub grid_color: u32,
+    pub alive_color: u32,
+    pub dead_color: u32,
+    /// The part of the Universe to draw, or all of it if not set.
+    #[wasm_bindgen(skip)]
+    pub crop: Option<Viewport>,
+}
+
+#[wasm_bindgen]
+impl RenderOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> RenderOptions {
+        RenderOptions::default()
+    }
+
+    /// Only draw the cells in a rectangle starting at the given row and
+    /// column.
+    pub fn set_crop(&mut self, row: u32, col: u32, width: u32, height: u32) {
+        self.crop = Some(Viewport {
+            row,
+            col,
+            width,
+            height,
+        });
+    }
+
+    /// Draw the whole Universe again.
+    pub fn clear_crop(&mut self) {
+        self.crop = None;
+    }
+}
+
+impl RenderOptions {
+    /// The cells to draw, cut down to those inside the Universe.
+    fn viewport(&self, universe: &Universe) -> Viewport {
+        let (width, height) = (universe.width(), universe.height());
+        let crop = self.crop.unwrap_or(Viewport {
+            row: 0,
+            col: 0,
+            width,
+            height,
+        });
+
+        let (row, col) = (crop.row.min(height), crop.col.min(width));
+        Viewport {
+            row,
+            col,
+            width: crop.width.min(width - col),
+            height: crop.height.min(height - row),
+        }
+    }
+
+    /// The width or height in pixels of a number of cells.
+    fn size(&self, cells: u32) -> u64 {
+        let grid = self.grid as u64;
+        cells as u64 * (self.cell_size as u64 + grid) + grid
+    }
+}
+
+impl Default for RenderOptions {
+    fn default() -> RenderOptions {
+        RenderOptions {
+            cell_size: 5,
+            grid: true,
+            grid_color: GRID_COLOR,
+            alive_color: ALIVE_COLOR,
+            dead_color: DEAD_COLOR,
+            crop: None,
+        }
+    }
+}
+
+/// A rectangle of cells.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Viewport {
+    pub row: u32,
+    pub col: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// A picture of the Universe as indices into a palette with an entry for
+/// each state and one for the grid.
+struct Image {
+    width: u32,
+    height: u32,
+    palette: Vec<u8>,
+    pixels: Vec<u8>,
+}
+
+impl Image {
+    fn draw(universe: &Universe, options: &RenderOptions) -> Result<Image, RenderError> {
+        let grid = options.grid as u32;
+        let pitch = options.cell_size + grid;
+        let view = options.viewport(universe);
+        let (width, height) = (options.size(view.width), options.size(view.height));
+        if width == 0 || height == 0 || width > MAX_IMAGE_SIZE || height > MAX_IMAGE_SIZE {
+            return Err(RenderError::Size { width, height });
+        }
+        let (width, height) = (width as u32, height as u32);
+
+        let states = universe.state_count();
+        let mut palette: Vec<u8> = (0..states)
+            .flat_map(|state| rgb(state_color(state, states, options)))
+            .collect();
+        palette.extend(rgb(options.grid_color));
+
+        let mut pixels = vec![states; (width * height) as usize];
+        for row in 0..view.height {
+            for col in 0..view.width {
+                let state = universe.get_state(view.row + row, view.col + col);
+                let (top, left) = (row * pitch + grid, col * pitch + grid);
+                for y in top..top + options.cell_size {
+                    let start = (y * width + left) as usize;
+                    pixels[start..start + options.cell_size as usize].fill(state);
+                }
+            }
+        }
+
+        Ok(Image {
+            width,
+            height,
+            palette,
+            pixels,
+        })
+    }
+}
+
+/// Dying cells of Generations rules fade from the alive colour towards the
+/// dead colour as they age.
+fn state_color(state: u8, states: u8, options: &RenderOptions) -> u32 {
+    if state == 0 {
+        return options.dead_color;
+    }
+
+    let (alive, dead) = (rgb(options.alive_color), rgb(options.dead_color));
+    let age = (state - 1) as u32;
+    let span = (states - 1) as u32;
+    (0..3).fold(0, |color, channel| {
+        let (from, to) = (alive[channel] as u32, dead[channel] as u32);
+        let mixed = (from * (span - age) + to * age + span / 2) / span;
+        color << 8 | mixed
+    })
+}
+
+fn rgb(color: u32) -> [u8; 3] {
+    [(color >> 16) as u8, (color >> 8) as u8, color as u8]
+}
+
+fn hex(color: u32) -> String {
+    format!("#{:06x}", color & 0xFF_FF_FF)
+}
+
+/// Draw the Universe as a PNG.
+pub(crate) fn png(universe: &Universe, options: &RenderOptions) -> Result<Vec<u8>, RenderError> {
+    let image = Image::draw(universe, options)?;
+    let mut bytes = Vec::new();
+
+    let mut encoder = png::Encoder::new(&mut bytes, image.width, image.height);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_palette(image.palette);
+    let mut writer = encoder.write_header().map_err(RenderError::encoding)?;
+    writer
+        .write_image_data(&image.pixels)
+        .map_err(RenderError::encoding)?;
+    writer.finish().map_err(RenderError::encoding)?;
+
+    Ok(bytes)
+}
+
+/// Draw a frame for each of the given number of generations, ticking the
+/// Universe between them, as a GIF that loops forever. The delay between
+/// frames is in hundredths of a second.
+pub(crate) fn gif(
+    universe: &mut Universe,
+    options: &RenderOptions,
+    generations: u32,
+    delay: u16,
+) -> Result<Vec<u8>, RenderError> {
+    let first = Image::draw(universe, options)?;
+    let (width, height) = (first.width as u16, first.height as u16);
+    let mut bytes = Vec::new();
+
+    let mut encoder = gif::Encoder::new(&mut bytes, width, height, &first.palette)
+        .map_err(RenderError::encoding)?;
+    encoder
+        .set_repeat(gif::Repeat::Infinite)
+        .map_err(RenderError::encoding)?;
+
+    let mut pixels = first.pixels;
+    for generation in 0..generations {
+        if generation > 0 {
+            universe.tick();
+            pixels = Image::draw(universe, options)?.pixels;
+        }
+
+        let mut frame =
+            gif::Frame::from_indexed_pixels(width, height, std::mem::take(&mut pixels), None);
+        frame.delay = delay;
+        encoder.write_frame(&frame).map_err(RenderError::encoding)?;
+    }
+    drop(encoder);
+
+    Ok(bytes)
+}
+
+/// Draw the Universe as an SVG image.
+///
+/// Dead cells are left to a background rectangle and each run of cells in
+/// the same state along a row is drawn as one rectangle, grouped by state.
+/// The grid goes on top as a single path.
+pub(crate) fn svg(universe: &Universe, options: &RenderOptions) -> String {
+    let view = options.viewport(universe);
+    let (width, height) = (options.size(view.width), options.size(view.height));
+    let grid = options.grid as u64;
+    let pitch = options.cell_size as u64 + grid;
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\" shape-rendering=\"crispEdges\">\n",
+        w = width,
+        h = height
+    );
+    svg += &format!(
+        "<rect width=\"{}\" height=\"{}\" fill=\"{}\"/>\n",
+        width,
+        height,
+        hex(options.dead_color)
+    );
+
+    let states = universe.state_count();
+    for state in 1..states {
+        let mut rects = String::new();
+        for row in 0..view.height {
+            let mut col = 0;
+            while col < view.width {
+                if universe.get_state(view.row + row, view.col + col) != state {
+                    col += 1;
+                    continue;
+                }
+
+                let start = col;
+                while col < view.width
+                    && universe.get_state(view.row + row, view.col + col) == state
+                {
+                    col += 1;
+                }
+                rects += &format!(
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"/>\n",
+                    start as u64 * pitch + grid,
+                    row as u64 * pitch + grid,
+                    (col - start) as u64 * pitch - grid,
+                    options.cell_size
+                );
+            }
+        }
+
+        if !rects.is_empty() {
+            svg += &format!(
+                "<g fill=\"{}\">\n{}</g>\n",
+                hex(state_color(state, states, options)),
+                rects
+            );
+        }
+    }
+
+    if options.grid {
+        let mut path = String::new();
+        for col in 0..=view.width {
+            path += &format!("M{}.5 0V{}", col as u64 * pitch, height);
+        }
+        for row in 0..=view.height {
+            path += &format!("M0 {}.5H{}", row as u64 * pitch, width);
+        }
+        svg += &format!(
+            "<path d=\"{}\" stroke=\"{}\"/>\n",
+            path,
+            hex(options.grid_color)
+        );
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Why a Universe couldn't be drawn.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RenderError {
+    /// The image would be empty, or wider or taller than 65535 pixels.
+    Size { width: u64, height: u64 },
+    /// The image encoder failed.
+    Encoding(String),
+}
+
+impl RenderError {
+    fn encoding(error: impl fmt::Display) -> RenderError {
+        RenderError::Encoding(error.to_string())
+    }
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RenderError::Size { width, height } => write!(
+                f,
+                "a {}x{} image can't be drawn, images must be between 1 and {} pixels on each side",
+                width, height, MAX_IMAGE_SIZE
+            ),
+            RenderError::Encoding(message) => write!(f, "couldn't encode the image: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for RenderError {}
+
+impl From<RenderError> for JsValue {
+    fn from(err: RenderError) -> JsValue {
+        JsValue::from_str(&err.to_string())
+    }
+}
@@ -0,0 +1,61 @@
+//! Where the existing cells go when a Universe is resized.
+
+use wasm_bindgen::prelude::*;
+
+/// The point of the Universe that stays put when it is resized. Cells keep
+/// their place relative to it, so a centre anchor grows or shrinks the
+/// Universe evenly on every side.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Anchor {
+    #[default]
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Centre,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    /// How far the cells move down and right when a Universe changes from
+    /// one size to another.
+    ///
+    /// A centred Universe that changes by an odd number of cells gets the
+    /// extra row or column at the bottom or right, so growing it and then
+    /// shrinking it back puts every cell where it started.
+    pub fn offset(self, from: (u32, u32), to: (u32, u32)) -> (i64, i64) {
+        let (from_width, from_height) = (from.0 as i64, from.1 as i64);
+        let (to_width, to_height) = (to.0 as i64, to.1 as i64);
+        let (width_change, height_change) = (to_width - from_width, to_height - from_height);
+
+        let row = match self {
+            Anchor::TopLeft | Anchor::Top | Anchor::TopRight => 0,
+            Anchor::Left | Anchor::Centre | Anchor::Right => height_change / 2,
+            Anchor::BottomLeft | Anchor::Bottom | Anchor::BottomRight => height_change,
+        };
+        let col = match self {
+            Anchor::TopLeft | Anchor::Left | Anchor::BottomLeft => 0,
+            Anchor::Top | Anchor::Centre | Anchor::Bottom => width_change / 2,
+            Anchor::TopRight | Anchor::Right | Anchor::BottomRight => width_change,
+        };
+        (row, col)
+    }
+}
+
+/// What happened to the cells when a Universe was resized.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Resized {
+    /// How many rows down the cells moved, negative if they moved up. A
+    /// `BigInt` in JavaScript, as the offset can be as large as a `u32`.
+    pub row_offset: i64,
+    /// How many columns right the cells moved, negative if they moved left.
+    pub col_offset: i64,
+    /// How many cells that weren't dead fell outside the new edges and were
+    /// lost.
+    pub cropped: u32,
+}
@@ -0,0 +1,658 @@
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use wasm_bindgen::JsValue;
+
+// Bits of a neighbourhood mask, clockwise from north.
+const N: u8 = 1 << 0;
+const NE: u8 = 1 << 1;
+const E: u8 = 1 << 2;
+const SE: u8 = 1 << 3;
+const S: u8 = 1 << 4;
+const SW: u8 = 1 << 5;
+const W: u8 = 1 << 6;
+const NW: u8 = 1 << 7;
+
+/// Hensel letters for 0 to 4 live neighbours, each with one representative
+/// neighbourhood. The letters for 5 to 8
+/// neighbours are those for 8 - n with the neighbourhood inverted.
+const HENSEL: [&[(char, u8)]; 5] = [
+    &[],
+    &[('c', NE), ('e', N)],
+    &[
+        ('c', NE | SE),
+        ('e', N | E),
+        ('a', N | NE),
+        ('i', N | S),
+        ('k', N | SE),
+        ('n', NE | SW),
+    ],
+    &[
+        ('c', NE | SE | SW),
+        ('e', N | E | S),
+        ('a', N | NE | E),
+        ('i', N | NE | NW),
+        ('k', N | E | SW),
+        ('n', N | NE | SE),
+        ('j', N | NE | W),
+        ('q', N | NE | SW),
+        ('r', N | NE | S),
+        ('y', N | SE | SW),
+    ],
+    &[
+        ('c', NE | SE | SW | NW),
+        ('e', N | E | S | W),
+        ('a', N | NE | E | SE),
+        ('i', N | NE | SE | S),
+        ('k', N | NE | SE | W),
+        ('n', N | NE | SE | NW),
+        ('j', N | NE | S | W),
+        ('q', N | NE | E | SW),
+        ('r', N | NE | E | S),
+        ('y', N | NE | SE | SW),
+        ('t', N | NE | S | NW),
+        ('w', N | NE | SW | W),
+        ('z', N | NE | S | SW),
+    ],
+];
+
+/// Bits of a `Rule` transition table entry.
+const BIRTH: u8 = 1;
+const SURVIVAL: u8 = 2;
+
+/// A Life-like rule, optionally isotropic non-totalistic and optionally from
+/// the Generations family, or a Larger than Life rule.
+///
+/// Life-like rules are stored as a lookup table indexed by the neighbourhood
+/// mask of a cell, with bits for the live neighbours clockwise from north
+/// (bit 0) to north-west (bit 7). Each entry says whether a dead cell with
+/// that neighbourhood is born and whether a live one survives, which covers
+/// both outer-totalistic rules like `B3/S23` and Hensel notation rules like
+/// `B2n3/S23-q`.
+///
+/// `states` is 2 for ordinary two-state rules. Generations rules have more
+/// states: a live cell that fails to survive passes through `states - 2`
+/// refractory (dying) states before it is dead again, and only cells in
+/// state 1 count as live neighbours.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rule {
+    transitions: Transitions,
+    states: u8,
+}
+
+// Rules are rarely copied, so keeping the table inline is not worth a Box.
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Transitions {
+    Table([u8; 256]),
+    LargerThanLife(LargerThanLife),
+}
+
+/// The cells counted as neighbours by a Larger than Life rule.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shape {
+    /// Every cell in the square of the given radius.
+    Moore,
+    /// Cells within the given Manhattan distance.
+    VonNeumann,
+    /// Cells within the given Euclidean distance, plus a half cell so the
+    /// edges of the circle are not ragged.
+    Circular,
+}
+
+/// A Larger than Life rule, counting live cells over an extended
+/// neighbourhood such as `R5,C0,M1,S34..58,B34..45,NM`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LargerThanLife {
+    radius: u32,
+    shape: Shape,
+    middle: bool,
+    birth: (u32, u32),
+    survival: (u32, u32),
+}
+
+/// Reasons a rulestring can be rejected.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RuleError {
+    /// The rulestring was empty.
+    Empty,
+    /// A character that is not a valid neighbour count was found.
+    InvalidCount(char),
+    /// A Hensel letter that does not exist for the given neighbour count.
+    InvalidLetter(u8, char),
+    /// The number of states was not between 2 and 255.
+    InvalidStates(String),
+    /// The topology suffix after the `:` was not recognised.
+    InvalidTopology(String),
+    /// The rulestring did not match any of the supported layouts.
+    Malformed(String),
+    /// The rule is valid but cannot be run by the engine it was given to.
+    Unsupported(String),
+}
+
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RuleError::Empty => write!(f, "rulestring is empty"),
+            RuleError::InvalidCount(c) => write!(f, "'{}' is not a valid neighbour count", c),
+            RuleError::InvalidLetter(count, c) => {
+                write!(f, "'{}' is not a valid letter for {} neighbours", c, count)
+            }
+            RuleError::InvalidStates(states) => {
+                write!(f, "'{}' is not a valid number of states (2-255)", states)
+            }
+            RuleError::InvalidTopology(suffix) => {
+                write!(f, "'{}' is not a recognised topology", suffix)
+            }
+            RuleError::Malformed(rule) => write!(f, "'{}' is not a recognised rulestring", rule),
+            RuleError::Unsupported(rule) => write!(f, "'{}' is not supported here", rule),
+        }
+    }
+}
+
+impl std::error::Error for RuleError {}
+
+impl From<RuleError> for JsValue {
+    fn from(err: RuleError) -> JsValue {
+        JsValue::from_str(&err.to_string())
+    }
+}
+
+impl Rule {
+    /// Conway's Game of Life, B3/S23.
+    pub fn conway() -> Rule {
+        let mut table = [0; 256];
+        for (neighbourhood, entry) in table.iter_mut().enumerate() {
+            match neighbourhood.count_ones() {
+                2 => *entry = SURVIVAL,
+                3 => *entry = BIRTH | SURVIVAL,
+                _ => (),
+            }
+        }
+
+        Rule {
+            transitions: Transitions::Table(table),
+            states: 2,
+        }
+    }
+
+    /// Parse a rulestring.
+    ///
+    /// Accepts `B3/S23` and `23/3` (survival/birth) notation for two-state
+    /// rules, and `B2/S345/C4` or `345/2/4` (survival/birth/states) for
+    /// Generations rules. Any count may be followed by Hensel letters to
+    /// include only those neighbourhoods (`B2n`) or by `-` and letters to
+    /// exclude them (`S2-a`).
+    ///
+    /// Larger than Life rules use Golly's `R5,C0,M1,S34..58,B34..45,NM`
+    /// notation.
+    pub fn parse(rule: &str) -> Result<Rule, RuleError> {
+        let rule = rule.trim();
+        if rule.is_empty() {
+            return Err(RuleError::Empty);
+        }
+        if rule.starts_with(['R', 'r']) && rule.contains(',') {
+            return LargerThanLife::parse(rule);
+        }
+        let malformed = || RuleError::Malformed(rule.to_string());
+
+        let parts: Vec<&str> = rule.split('/').collect();
+        let lettered = parts
+            .iter()
+            .any(|part| part.starts_with(|c: char| c.is_ascii_alphabetic()));
+
+        let (birth, survival, states) = if lettered {
+            let mut birth = None;
+            let mut survival = None;
+            let mut states = None;
+
+            for part in parts.iter().flat_map(|part| Self::split_fields(part)) {
+                let mut chars = part.chars();
+                let field = match chars.next().map(|c| c.to_ascii_uppercase()) {
+                    Some('B') => &mut birth,
+                    Some('S') => &mut survival,
+                    Some('C') | Some('G') => &mut states,
+                    _ => return Err(malformed()),
+                };
+                if field.replace(chars.as_str()).is_some() {
+                    return Err(malformed());
+                }
+            }
+
+            (
+                birth.ok_or_else(malformed)?,
+                survival.ok_or_else(malformed)?,
+                states,
+            )
+        } else {
+            match parts.as_slice() {
+                [survival, birth] => (*birth, *survival, None),
+                [survival, birth, states] => (*birth, *survival, Some(*states)),
+                _ => return Err(malformed()),
+            }
+        };
+
+        let mut table = [0; 256];
+        Self::parse_conditions(birth, BIRTH, &mut table)?;
+        Self::parse_conditions(survival, SURVIVAL, &mut table)?;
+
+        Ok(Rule {
+            transitions: Transitions::Table(table),
+            states: states.map_or(Ok(2), Self::parse_states)?,
+        })
+    }
+
+    /// The number of cell states, 2 for ordinary Life-like rules.
+    pub fn states(&self) -> u8 {
+        self.states
+    }
+
+    /// The Larger than Life parameters, if this is a Larger than Life rule.
+    pub fn larger_than_life(&self) -> Option<&LargerThanLife> {
+        match &self.transitions {
+            Transitions::LargerThanLife(ltl) => Some(ltl),
+            Transitions::Table(_) => None,
+        }
+    }
+
+    /// How far away a cell can be and still count as a neighbour.
+    pub fn radius(&self) -> u32 {
+        self.larger_than_life().map_or(1, |ltl| ltl.radius)
+    }
+
+    /// Whether the rule only depends on the number of live neighbours rather
+    /// than their arrangement.
+    pub fn is_totalistic(&self) -> bool {
+        match &self.transitions {
+            Transitions::Table(table) => (0..=255u8).all(|neighbourhood| {
+                let count = neighbourhood.count_ones();
+                table[neighbourhood as usize] == table[(1usize << count) - 1]
+            }),
+            Transitions::LargerThanLife(_) => true,
+        }
+    }
+
+    /// The birth and survival conditions as bitmasks over neighbour counts,
+    /// for two-state rules on the immediate neighbourhood that only depend
+    /// on the count.
+    pub(crate) fn count_masks(&self) -> Option<(u16, u16)> {
+        match &self.transitions {
+            Transitions::Table(table) if self.states == 2 && self.is_totalistic() => {
+                let mask = |condition| {
+                    (0..=8)
+                        .filter(|&count| table[(1usize << count) - 1] & condition != 0)
+                        .fold(0, |mask, count| mask | 1 << count)
+                };
+                Some((mask(BIRTH), mask(SURVIVAL)))
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether dead cells with no live neighbours come alive, which makes
+    /// the rule unusable on an unbounded plane.
+    pub fn births_from_nothing(&self) -> bool {
+        match &self.transitions {
+            Transitions::Table(table) => table[0] & BIRTH != 0,
+            Transitions::LargerThanLife(ltl) => ltl.birth.0 == 0,
+        }
+    }
+
+    /// Whether a cell with the given state is alive in the next generation,
+    /// given the mask of its live neighbours clockwise from north.
+    ///
+    /// Larger than Life rules only see the immediate neighbours here, use
+    /// `LargerThanLife::next_state` with a full count instead.
+    pub fn next_state(&self, alive: bool, neighbourhood: u8) -> bool {
+        match &self.transitions {
+            Transitions::Table(table) => {
+                let condition = if alive { SURVIVAL } else { BIRTH };
+                table[neighbourhood as usize] & condition != 0
+            }
+            Transitions::LargerThanLife(ltl) => ltl.next_state(
+                alive,
+                neighbourhood.count_ones() + (alive && ltl.middle) as u32,
+            ),
+        }
+    }
+
+    /// The next state of a cell under a Generations rule, where state 0 is
+    /// dead, 1 is alive and anything higher is dying.
+    pub fn next_generations_state(&self, state: u8, neighbourhood: u8) -> u8 {
+        self.decay(state, self.next_state(state == 1, neighbourhood))
+    }
+
+    /// The next state of a cell given its current state and whether the
+    /// birth or survival condition for it holds, decaying live cells that do
+    /// not survive through the dying states.
+    pub fn decay(&self, state: u8, live: bool) -> u8 {
+        match state {
+            0 => live as u8,
+            1 if live => 1,
+            dying => (dying + 1) % self.states,
+        }
+    }
+
+    /// The Hensel letters available for a number of live neighbours, each
+    /// with a representative neighbourhood.
+    fn hensel_letters(count: u8) -> impl Iterator<Item = (char, u8)> {
+        let (letters, invert) = if count <= 4 {
+            (HENSEL[count as usize], 0)
+        } else {
+            (HENSEL[8 - count as usize], 0xff)
+        };
+
+        letters
+            .iter()
+            .map(move |&(letter, neighbourhood)| (letter, neighbourhood ^ invert))
+    }
+
+    /// All rotations and reflections of a neighbourhood.
+    fn symmetries(neighbourhood: u8) -> impl Iterator<Item = u8> {
+        // Reflect in the north-south axis: N and S are fixed while E swaps
+        // with W, NE with NW and SE with SW.
+        let reflected = (0..8)
+            .filter(|bit| neighbourhood & (1 << bit) != 0)
+            .fold(0u8, |mask, bit| mask | 1 << ((8 - bit) % 8));
+
+        // Rotating by 90 degrees moves every neighbour two bits around.
+        (0..4).flat_map(move |turns| {
+            [
+                neighbourhood.rotate_left(2 * turns),
+                reflected.rotate_left(2 * turns),
+            ]
+        })
+    }
+
+    /// Whether two neighbourhoods are rotations or reflections of each other.
+    fn is_equivalent(a: u8, b: u8) -> bool {
+        Self::symmetries(a).any(|symmetry| symmetry == b)
+    }
+
+    fn table(&self) -> &[u8; 256] {
+        match &self.transitions {
+            Transitions::Table(table) => table,
+            Transitions::LargerThanLife(_) => unreachable!("Larger than Life rules have no table"),
+        }
+    }
+
+    /// Parse the birth or survival half of a rulestring, such as `2n3` or
+    /// `23-q`, setting `condition` in the entries of `table` it covers.
+    fn parse_conditions(
+        conditions: &str,
+        condition: u8,
+        table: &mut [u8; 256],
+    ) -> Result<(), RuleError> {
+        let mut chars = conditions.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            let count = match c.to_digit(10) {
+                Some(count) if count <= 8 => count as u8,
+                _ => return Err(RuleError::InvalidCount(c)),
+            };
+
+            let exclude = chars.next_if_eq(&'-').is_some();
+            let mut letters = Vec::new();
+            while let Some(letter) = chars.next_if(|c| c.is_ascii_lowercase()) {
+                match Self::hensel_letters(count).find(|&(l, _)| l == letter) {
+                    Some((_, neighbourhood)) => letters.push(neighbourhood),
+                    None => return Err(RuleError::InvalidLetter(count, letter)),
+                }
+            }
+            // A '-' must be followed by the letters to leave out.
+            if exclude && letters.is_empty() {
+                return Err(RuleError::InvalidLetter(count, '-'));
+            }
+
+            for neighbourhood in (0..=255u8).filter(|n| n.count_ones() == count as u32) {
+                let listed = letters
+                    .iter()
+                    .any(|&letter| Self::is_equivalent(letter, neighbourhood));
+                let included = if letters.is_empty() {
+                    true
+                } else {
+                    listed != exclude
+                };
+
+                if included {
+                    table[neighbourhood as usize] |= condition;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Split a `B3S23` style field into its birth and survival halves.
+    fn split_fields(part: &str) -> Vec<&str> {
+        match part.find(['S', 's']) {
+            Some(idx) if idx > 0 && part.starts_with(['B', 'b']) => {
+                vec![&part[..idx], &part[idx..]]
+            }
+            _ => vec![part],
+        }
+    }
+
+    fn parse_states(states: &str) -> Result<u8, RuleError> {
+        match states.parse::<u8>() {
+            // Golly treats C0 and C1 as plain two-state rules.
+            Ok(0..=2) => Ok(2),
+            Ok(states) => Ok(states),
+            Err(_) => Err(RuleError::InvalidStates(states.to_string())),
+        }
+    }
+
+    /// Write the birth or survival half of the rule, using Hensel letters
+    /// for any count that only applies to some neighbourhoods.
+    fn write_conditions(&self, f: &mut fmt::Formatter, condition: u8) -> fmt::Result {
+        for count in 0..=8u8 {
+            let letters: Vec<(char, bool)> = Self::hensel_letters(count)
+                .map(|(letter, neighbourhood)| {
+                    (
+                        letter,
+                        self.table()[neighbourhood as usize] & condition != 0,
+                    )
+                })
+                .collect();
+
+            if letters.is_empty() {
+                // 0 and 8 neighbours only have one arrangement.
+                if self.table()[(1usize << count) - 1] & condition != 0 {
+                    write!(f, "{}", count)?;
+                }
+                continue;
+            }
+
+            let included = letters.iter().filter(|&&(_, set)| set).count();
+            if included == 0 {
+                continue;
+            }
+
+            write!(f, "{}", count)?;
+            if included == letters.len() {
+                continue;
+            }
+
+            // Prefer whichever of the included or excluded letters is shorter.
+            let exclude = included > letters.len() - included;
+            if exclude {
+                write!(f, "-")?;
+            }
+            for (letter, set) in letters {
+                if set != exclude {
+                    write!(f, "{}", letter)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for Rule {
+    fn default() -> Rule {
+        Rule::conway()
+    }
+}
+
+impl FromStr for Rule {
+    type Err = RuleError;
+
+    fn from_str(rule: &str) -> Result<Rule, RuleError> {
+        Rule::parse(rule)
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ltl) = self.larger_than_life() {
+            let states = if self.states > 2 { self.states } else { 0 };
+            return write!(
+                f,
+                "R{},C{},M{},S{}..{},B{}..{},N{}",
+                ltl.radius,
+                states,
+                ltl.middle as u8,
+                ltl.survival.0,
+                ltl.survival.1,
+                ltl.birth.0,
+                ltl.birth.1,
+                match ltl.shape {
+                    Shape::Moore => 'M',
+                    Shape::VonNeumann => 'N',
+                    Shape::Circular => 'C',
+                }
+            );
+        }
+
+        write!(f, "B")?;
+        self.write_conditions(f, BIRTH)?;
+        write!(f, "/S")?;
+        self.write_conditions(f, SURVIVAL)?;
+        if self.states > 2 {
+            write!(f, "/C{}", self.states)?;
+        }
+        Ok(())
+    }
+}
+
+impl LargerThanLife {
+    /// The largest radius accepted, matching Golly.
+    pub const MAX_RADIUS: u32 = 500;
+
+    fn parse(rule: &str) -> Result<Rule, RuleError> {
+        let malformed = || RuleError::Malformed(rule.to_string());
+        let mut radius = None;
+        let mut states = None;
+        let mut middle = false;
+        let mut birth = None;
+        let mut survival = None;
+        let mut shape = Shape::Moore;
+
+        for part in rule.split(',').map(str::trim) {
+            let mut chars = part.chars();
+            let field = chars.next().map(|c| c.to_ascii_uppercase());
+            let value = chars.as_str();
+
+            match field {
+                Some('R') => radius = Some(value.parse::<u32>().map_err(|_| malformed())?),
+                Some('C') => states = Some(Rule::parse_states(value)?),
+                Some('M') => {
+                    middle = match value {
+                        "0" => false,
+                        "1" => true,
+                        _ => return Err(malformed()),
+                    }
+                }
+                Some('S') => survival = Some(Self::parse_range(value).ok_or_else(malformed)?),
+                Some('B') => birth = Some(Self::parse_range(value).ok_or_else(malformed)?),
+                Some('N') => {
+                    shape = match value.to_ascii_uppercase().as_str() {
+                        "M" => Shape::Moore,
+                        "N" => Shape::VonNeumann,
+                        "C" => Shape::Circular,
+                        _ => return Err(malformed()),
+                    }
+                }
+                _ => return Err(malformed()),
+            }
+        }
+
+        let radius = radius
+            .filter(|radius| (1..=Self::MAX_RADIUS).contains(radius))
+            .ok_or_else(malformed)?;
+
+        Ok(Rule {
+            transitions: Transitions::LargerThanLife(LargerThanLife {
+                radius,
+                shape,
+                middle,
+                birth: birth.ok_or_else(malformed)?,
+                survival: survival.ok_or_else(malformed)?,
+            }),
+            states: states.unwrap_or(2),
+        })
+    }
+
+    /// Parse a `34..58` count range.
+    fn parse_range(range: &str) -> Option<(u32, u32)> {
+        let (min, max) = range.split_once("..")?;
+        let (min, max) = (min.parse().ok()?, max.parse().ok()?);
+        if min <= max {
+            Some((min, max))
+        } else {
+            None
+        }
+    }
+
+    /// How far away a cell can be and still count as a neighbour.
+    pub fn radius(&self) -> u32 {
+        self.radius
+    }
+
+    /// The shape of the neighbourhood.
+    pub fn shape(&self) -> Shape {
+        self.shape
+    }
+
+    /// Whether a live cell counts itself as one of its neighbours.
+    pub fn includes_middle(&self) -> bool {
+        self.middle
+    }
+
+    /// The counts at which a dead cell comes alive.
+    pub fn birth(&self) -> RangeInclusive<u32> {
+        self.birth.0..=self.birth.1
+    }
+
+    /// The counts at which a live cell survives.
+    pub fn survival(&self) -> RangeInclusive<u32> {
+        self.survival.0..=self.survival.1
+    }
+
+    /// How many cells either side of the centre are included on the row
+    /// `delta` rows above or below it.
+    pub fn half_width(&self, delta: u32) -> u32 {
+        let radius = self.radius;
+        match self.shape {
+            Shape::Moore => radius,
+            Shape::VonNeumann => radius - delta,
+            Shape::Circular => {
+                // Within radius + 0.5 of the centre, so x^2 + y^2 <= r^2 + r.
+                let limit = radius * radius + radius - delta * delta;
+                (0..=radius).rev().find(|x| x * x <= limit).unwrap_or(0)
+            }
+        }
+    }
+
+    /// Whether a cell is alive in the next generation given the number of
+    /// live cells in its neighbourhood. The count should include the cell
+    /// itself if `includes_middle` is set.
+    pub fn next_state(&self, alive: bool, count: u32) -> bool {
+        if alive {
+            self.survival().contains(&count)
+        } else {
+            self.birth().contains(&count)
+        }
+    }
+}
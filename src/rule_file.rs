@@ -0,0 +1,726 @@
+//! Golly's `.rule` files, which define rules with any number of states by a
+//! table of transitions in a `@TABLE` section or a decision tree in a
+//! `@TREE` section.
+//!
+//! ```text
+//! @RULE WireWorld
+//! @TABLE
+//! n_states:4
+//! neighborhood:Moore
+//! symmetries:permute
+//! var a={0,1,2,3}
+//! ...
+//! 1,a,b,c,d,e,f,g,h,2
+//! ```
+//!
+//! Only the Moore and von Neumann neighbourhoods are supported. Other
+//! sections such as `@COLORS` and `@ICONS` are skipped.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use wasm_bindgen::prelude::*;
+
+/// A cell and its neighbours: the cell itself, then north, north-east and
+/// on clockwise to north-west.
+pub(crate) type Neighbourhood = [u8; 9];
+
+/// The cells of a `Neighbourhood` a Moore table lists, in its order.
+const MOORE: &[usize] = &[0, 1, 2, 3, 4, 5, 6, 7, 8];
+/// The cells of a `Neighbourhood` a von Neumann table lists: the cell,
+/// north, east, south and west.
+const VON_NEUMANN: &[usize] = &[0, 1, 3, 5, 7];
+/// The order a Moore tree branches on cells: north-west, north-east,
+/// south-west, south-east, north, west, east, south and the cell itself.
+const TREE_MOORE: &[usize] = &[8, 2, 6, 4, 1, 7, 3, 5, 0];
+/// The order a von Neumann tree branches on cells: north, west, east, south
+/// and the cell itself.
+const TREE_VON_NEUMANN: &[usize] = &[1, 7, 3, 5, 0];
+
+/// A rule loaded from a Golly `.rule` file.
+#[derive(Clone, Debug)]
+pub struct GollyRule {
+    name: String,
+    states: u8,
+    /// The cells of a `Neighbourhood` the rule looks at, in the order the
+    /// table or tree takes them.
+    cells: &'static [usize],
+    engine: Engine,
+    /// Results already worked out, keyed by the neighbourhood with any
+    /// cells the rule ignores cleared.
+    cache: HashMap<Neighbourhood, u8>,
+    source: String,
+}
+
+#[derive(Clone, Debug)]
+enum Engine {
+    Table(Table),
+    Tree(Tree),
+}
+
+impl GollyRule {
+    pub fn parse(text: &str) -> Result<GollyRule, RuleFileError> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(line, text)| (line + 1, text.split('#').next().unwrap_or("").trim()))
+            .filter(|(_, text)| !text.is_empty());
+
+        let name = match lines.next() {
+            Some((_, text)) if text.starts_with("@RULE") => text["@RULE".len()..].trim(),
+            Some((line, _)) => return Err(RuleFileError::new(line, "expected '@RULE'")),
+            None => return Err(RuleFileError::new(1, "the file is empty")),
+        };
+        if name.is_empty() {
+            return Err(RuleFileError::new(1, "the rule needs a name"));
+        }
+
+        // Split the rest into sections, keeping only the table or tree.
+        let mut section = None;
+        let mut table = Vec::new();
+        let mut tree = Vec::new();
+        for (line, text) in lines {
+            if text.starts_with('@') {
+                section = text.split_whitespace().next();
+                continue;
+            }
+            match section {
+                Some("@TABLE") => table.push((line, text)),
+                Some("@TREE") => tree.push((line, text)),
+                _ => (),
+            }
+        }
+
+        let (states, cells, engine) = match (table.is_empty(), tree.is_empty()) {
+            (false, true) => {
+                let (states, cells, table) = Table::parse(&table)?;
+                (states, cells, Engine::Table(table))
+            }
+            (true, false) => {
+                let (states, cells, tree) = Tree::parse(&tree)?;
+                (states, cells, Engine::Tree(tree))
+            }
+            (false, false) => {
+                return Err(RuleFileError::new(
+                    tree[0].0,
+                    "a rule can't have both a table and a tree",
+                ))
+            }
+            (true, true) => {
+                return Err(RuleFileError::new(
+                    1,
+                    "the file has no @TABLE or @TREE section",
+                ))
+            }
+        };
+
+        Ok(GollyRule {
+            name: name.to_string(),
+            states,
+            cells,
+            engine,
+            cache: HashMap::new(),
+            source: text.to_string(),
+        })
+    }
+
+    /// The name given by the `@RULE` line.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn states(&self) -> u8 {
+        self.states
+    }
+
+    /// The text the rule was read from.
+    pub(crate) fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The next state of the cell at the middle of a neighbourhood.
+    pub fn next_state(&mut self, neighbourhood: &Neighbourhood) -> u8 {
+        let mut key = [0; 9];
+        for &cell in self.cells {
+            key[cell] = neighbourhood[cell];
+        }
+        if let Some(&state) = self.cache.get(&key) {
+            return state;
+        }
+
+        let cells: Vec<u8> = self.cells.iter().map(|&cell| key[cell]).collect();
+        let state = match &self.engine {
+            Engine::Table(table) => table.next_state(&cells),
+            Engine::Tree(tree) => tree.next_state(&cells),
+        };
+        self.cache.insert(key, state);
+        state
+    }
+}
+
+/// A set of states, one bit per state.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+struct StateSet([u64; 4]);
+
+impl StateSet {
+    fn single(state: u8) -> StateSet {
+        let mut set = StateSet::default();
+        set.insert(state);
+        set
+    }
+
+    fn insert(&mut self, state: u8) {
+        self.0[state as usize / 64] |= 1 << (state % 64);
+    }
+
+    fn contains(&self, state: u8) -> bool {
+        self.0[state as usize / 64] & 1 << (state % 64) != 0
+    }
+
+    fn states(&self) -> impl Iterator<Item = u8> + '_ {
+        (0..=u8::MAX).filter(move |&state| self.contains(state))
+    }
+}
+
+/// One entry of a transition line, before variables are bound.
+#[derive(Clone, Debug)]
+enum Entry {
+    State(u8),
+    Variable(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct Transition {
+    /// The states allowed for the cell and then each neighbour.
+    inputs: Vec<StateSet>,
+    output: u8,
+}
+
+/// A list of transitions, the first to match a neighbourhood giving the new
+/// state. Cells that match none keep their state.
+#[derive(Clone, Debug)]
+struct Table {
+    transitions: Vec<Transition>,
+    /// The neighbours may be in any order, so transitions are matched
+    /// against them as a set rather than being written out in every order.
+    permute: bool,
+}
+
+impl Table {
+    fn parse(lines: &[(usize, &str)]) -> Result<(u8, &'static [usize], Table), RuleFileError> {
+        let mut states = None;
+        let mut cells = MOORE;
+        let mut symmetries = (lines[0].0, "none");
+        let mut variables: HashMap<&str, StateSet> = HashMap::new();
+        let mut transitions = Vec::new();
+        let mut permute = false;
+        let mut seen = HashSet::new();
+
+        for &(line, text) in lines {
+            if let Some(value) = text.strip_prefix("n_states:") {
+                states = Some(parse_states(line, value)?);
+            } else if let Some(value) = text.strip_prefix("neighborhood:") {
+                cells = match value.trim() {
+                    "Moore" => MOORE,
+                    "vonNeumann" => VON_NEUMANN,
+                    other => {
+                        return Err(RuleFileError::new(
+                            line,
+                            format!("the {} neighbourhood is not supported", other),
+                        ))
+                    }
+                };
+            } else if let Some(value) = text.strip_prefix("symmetries:") {
+                symmetries = (line, value.trim());
+            } else if let Some(definition) = text.strip_prefix("var ") {
+                let states = states.ok_or_else(|| {
+                    RuleFileError::new(line, "n_states must come before any variables")
+                })?;
+                let (name, values) = parse_variable(line, definition, states, &variables)?;
+                variables.insert(name, values);
+            } else {
+                let states = states.ok_or_else(|| {
+                    RuleFileError::new(line, "n_states must come before any transitions")
+                })?;
+                let (orders, permutes) =
+                    symmetry_orders(symmetries.0, symmetries.1, cells.len() - 1)?;
+                permute = permutes;
+
+                let entries = parse_transition(line, text, states, &variables, cells.len() + 1)?;
+                for transition in bind(line, &entries, &variables)? {
+                    for order in &orders {
+                        let mut inputs = vec![transition.inputs[0]];
+                        inputs.extend(order.iter().map(|&cell| transition.inputs[cell + 1]));
+                        let transition = Transition {
+                            inputs,
+                            output: transition.output,
+                        };
+                        if seen.insert(transition.clone()) {
+                            transitions.push(transition);
+                        }
+                    }
+                }
+            }
+        }
+
+        let states =
+            states.ok_or_else(|| RuleFileError::new(lines[0].0, "the table needs n_states"))?;
+        Ok((
+            states,
+            cells,
+            Table {
+                transitions,
+                permute,
+            },
+        ))
+    }
+
+    fn next_state(&self, cells: &[u8]) -> u8 {
+        self.transitions
+            .iter()
+            .find(|transition| {
+                let (cell, neighbours) = transition
+                    .inputs
+                    .split_first()
+                    .expect("transitions have a cell");
+                cell.contains(cells[0])
+                    && if self.permute {
+                        matches_in_any_order(neighbours, &cells[1..])
+                    } else {
+                        neighbours
+                            .iter()
+                            .zip(&cells[1..])
+                            .all(|(set, &state)| set.contains(state))
+                    }
+            })
+            .map_or(cells[0], |transition| transition.output)
+    }
+}
+
+fn parse_states(line: usize, value: &str) -> Result<u8, RuleFileError> {
+    match value.trim().parse::<u32>() {
+        Ok(states) if (2..=255).contains(&states) => Ok(states as u8),
+        _ => Err(RuleFileError::new(
+            line,
+            format!("'{}' is not a valid number of states (2-255)", value.trim()),
+        )),
+    }
+}
+
+/// Parse a state, or the name of a variable.
+fn parse_entry(
+    line: usize,
+    text: &str,
+    states: u8,
+    variables: &HashMap<&str, StateSet>,
+) -> Result<Entry, RuleFileError> {
+    match text.parse::<u32>() {
+        Ok(state) if state < states as u32 => Ok(Entry::State(state as u8)),
+        Ok(state) => Err(RuleFileError::new(
+            line,
+            format!("state {} is out of range", state),
+        )),
+        Err(_) if variables.contains_key(text) => Ok(Entry::Variable(text.to_string())),
+        Err(_) => Err(RuleFileError::new(
+            line,
+            format!("'{}' is not a state or variable", text),
+        )),
+    }
+}
+
+/// Parse a variable definition such as `a={0,1,2}`, whose values may
+/// include other variables.
+fn parse_variable<'a>(
+    line: usize,
+    definition: &'a str,
+    states: u8,
+    variables: &HashMap<&str, StateSet>,
+) -> Result<(&'a str, StateSet), RuleFileError> {
+    let (name, values) = definition
+        .split_once('=')
+        .ok_or_else(|| RuleFileError::new(line, "expected 'var name={...}'"))?;
+    let values = values
+        .trim()
+        .strip_prefix('{')
+        .and_then(|values| values.strip_suffix('}'))
+        .ok_or_else(|| RuleFileError::new(line, "variable values must be in braces"))?;
+
+    let mut set = StateSet::default();
+    for value in values.split(',') {
+        match parse_entry(line, value.trim(), states, variables)? {
+            Entry::State(state) => set.insert(state),
+            Entry::Variable(other) => {
+                for state in variables[other.as_str()].states() {
+                    set.insert(state);
+                }
+            }
+        }
+    }
+    Ok((name.trim(), set))
+}
+
+/// Parse a transition, written either with commas or, when every state is
+/// a single digit, as a run of characters.
+fn parse_transition(
+    line: usize,
+    text: &str,
+    states: u8,
+    variables: &HashMap<&str, StateSet>,
+    length: usize,
+) -> Result<Vec<Entry>, RuleFileError> {
+    let entries: Vec<String> = if text.contains(',') {
+        text.split(',')
+            .map(|entry| entry.trim().to_string())
+            .collect()
+    } else {
+        text.chars()
+            .filter(|c| !c.is_whitespace())
+            .map(String::from)
+            .collect()
+    };
+    if entries.len() != length {
+        return Err(RuleFileError::new(
+            line,
+            format!("expected {} entries, found {}", length, entries.len()),
+        ));
+    }
+
+    entries
+        .iter()
+        .map(|entry| parse_entry(line, entry, states, variables))
+        .collect()
+}
+
+/// Turn a transition into ones without bound variables.
+///
+/// A variable that appears more than once in a transition, including as
+/// the output, takes the same state everywhere it appears, so the
+/// transition is written out once for each of its states.
+fn bind(
+    line: usize,
+    entries: &[Entry],
+    variables: &HashMap<&str, StateSet>,
+) -> Result<Vec<Transition>, RuleFileError> {
+    let (inputs, output) = entries.split_at(entries.len() - 1);
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for entry in entries {
+        if let Entry::Variable(name) = entry {
+            *counts.entry(name.as_str()).or_insert(0) += 1;
+        }
+    }
+    if let Entry::Variable(name) = &output[0] {
+        if counts[name.as_str()] == 1 {
+            return Err(RuleFileError::new(
+                line,
+                format!("the output variable '{}' isn't used in the inputs", name),
+            ));
+        }
+    }
+
+    let bound: Vec<&str> = {
+        let mut bound: Vec<&str> = counts
+            .iter()
+            .filter(|&(_, &count)| count > 1)
+            .map(|(&name, _)| name)
+            .collect();
+        bound.sort_unstable();
+        bound
+    };
+
+    let mut transitions = Vec::new();
+    let mut values = HashMap::new();
+    bind_next(&bound, variables, &mut values, &mut |values| {
+        let set = |entry: &Entry| match entry {
+            Entry::State(state) => StateSet::single(*state),
+            Entry::Variable(name) => values
+                .get(name.as_str())
+                .map_or(variables[name.as_str()], |&state| StateSet::single(state)),
+        };
+        let output = match &output[0] {
+            Entry::State(state) => *state,
+            Entry::Variable(name) => values[name.as_str()],
+        };
+        transitions.push(Transition {
+            inputs: inputs.iter().map(set).collect(),
+            output,
+        });
+    });
+    Ok(transitions)
+}
+
+/// Call `visit` with every combination of states for the bound variables.
+fn bind_next<'a>(
+    bound: &[&'a str],
+    variables: &HashMap<&str, StateSet>,
+    values: &mut HashMap<&'a str, u8>,
+    visit: &mut impl FnMut(&HashMap<&'a str, u8>),
+) {
+    match bound.split_first() {
+        None => visit(values),
+        Some((&name, rest)) => {
+            for state in variables[name].states() {
+                values.insert(name, state);
+                bind_next(rest, variables, values, visit);
+            }
+            values.remove(name);
+        }
+    }
+}
+
+/// The orders to write the neighbours of a transition in to cover its
+/// symmetries, and whether they may instead appear in any order.
+fn symmetry_orders(
+    line: usize,
+    symmetries: &str,
+    neighbours: usize,
+) -> Result<(Vec<Vec<usize>>, bool), RuleFileError> {
+    let rotations = |step: usize| -> Vec<Vec<usize>> {
+        (0..neighbours)
+            .step_by(step)
+            .map(|turn| {
+                (0..neighbours)
+                    .map(|cell| (cell + turn) % neighbours)
+                    .collect()
+            })
+            .collect()
+    };
+    let reflected = |orders: Vec<Vec<usize>>| -> Vec<Vec<usize>> {
+        let mirror: Vec<Vec<usize>> = orders
+            .iter()
+            .map(|order| {
+                order
+                    .iter()
+                    .map(|&cell| (neighbours - cell) % neighbours)
+                    .collect()
+            })
+            .collect();
+        orders.into_iter().chain(mirror).collect()
+    };
+
+    let (half, quarter, eighth) = (neighbours / 2, neighbours / 4, neighbours / 8);
+    let orders = match symmetries {
+        "none" => rotations(neighbours),
+        "permute" => return Ok((rotations(neighbours), true)),
+        "rotate2" => rotations(half),
+        "rotate4" => rotations(quarter),
+        "rotate8" if eighth > 0 => rotations(eighth),
+        "reflect_horizontal" => reflected(rotations(neighbours)),
+        "rotate2reflect" => reflected(rotations(half)),
+        "rotate4reflect" => reflected(rotations(quarter)),
+        "rotate8reflect" if eighth > 0 => reflected(rotations(eighth)),
+        _ => {
+            return Err(RuleFileError::new(
+                line,
+                format!(
+                    "'{}' symmetry is not supported for this neighbourhood",
+                    symmetries
+                ),
+            ))
+        }
+    };
+    Ok((orders, false))
+}
+
+/// Whether each neighbour can be given a different one of the sets that
+/// allows its state, found by augmenting paths.
+fn matches_in_any_order(sets: &[StateSet], cells: &[u8]) -> bool {
+    fn assign(
+        cell: usize,
+        sets: &[StateSet],
+        cells: &[u8],
+        owners: &mut [Option<usize>],
+        tried: &mut [bool],
+    ) -> bool {
+        for set in 0..sets.len() {
+            if tried[set] || !sets[set].contains(cells[cell]) {
+                continue;
+            }
+            tried[set] = true;
+            if owners[set].is_none_or(|other| assign(other, sets, cells, owners, tried)) {
+                owners[set] = Some(cell);
+                return true;
+            }
+        }
+        false
+    }
+
+    let mut owners = vec![None; sets.len()];
+    (0..cells.len())
+        .all(|cell| assign(cell, sets, cells, &mut owners, &mut vec![false; sets.len()]))
+}
+
+/// A decision tree branching on one cell at each level, down to leaves
+/// giving the new state.
+#[derive(Clone, Debug)]
+struct Tree {
+    states: usize,
+    /// Each node's branches, one per state, packed one after another.
+    /// Branches of the lowest nodes are states, the rest are node numbers.
+    branches: Vec<u32>,
+    root: usize,
+}
+
+impl Tree {
+    fn parse(lines: &[(usize, &str)]) -> Result<(u8, &'static [usize], Tree), RuleFileError> {
+        let mut states = None;
+        let mut cells = None;
+        let mut expected_nodes = None;
+        let mut levels = Vec::new();
+        let mut branches = Vec::new();
+
+        for &(line, text) in lines {
+            if let Some((key, value)) = text.split_once('=') {
+                let value = value.trim();
+                match key.trim() {
+                    "num_states" => states = Some(parse_states(line, value)?),
+                    "num_neighbors" => {
+                        cells = Some(match value {
+                            "8" => TREE_MOORE,
+                            "4" => TREE_VON_NEUMANN,
+                            _ => {
+                                return Err(RuleFileError::new(
+                                    line,
+                                    format!("{} neighbours are not supported", value),
+                                ))
+                            }
+                        })
+                    }
+                    "num_nodes" => {
+                        expected_nodes = Some(value.parse::<usize>().map_err(|_| {
+                            RuleFileError::new(line, format!("invalid node count '{}'", value))
+                        })?)
+                    }
+                    key => {
+                        return Err(RuleFileError::new(
+                            line,
+                            format!("unknown setting '{}'", key),
+                        ))
+                    }
+                }
+                continue;
+            }
+
+            let (states, cells) = match (states, cells) {
+                (Some(states), Some(cells)) => (states as usize, cells),
+                _ => {
+                    return Err(RuleFileError::new(
+                        line,
+                        "num_states and num_neighbors must come before the nodes",
+                    ))
+                }
+            };
+            let values = text
+                .split_whitespace()
+                .map(|value| value.parse::<u32>())
+                .collect::<Result<Vec<u32>, _>>()
+                .map_err(|_| RuleFileError::new(line, "nodes must be lists of numbers"))?;
+            let (level, node_branches) = match values.split_first() {
+                Some((&level, rest)) if rest.len() == states => (level as usize, rest),
+                _ => {
+                    return Err(RuleFileError::new(
+                        line,
+                        format!("expected a level and {} branches", states),
+                    ))
+                }
+            };
+            if level == 0 || level > cells.len() {
+                return Err(RuleFileError::new(
+                    line,
+                    format!("level {} is out of range", level),
+                ));
+            }
+
+            for &branch in node_branches {
+                let valid = if level == 1 {
+                    (branch as usize) < states
+                } else {
+                    levels.get(branch as usize) == Some(&(level - 1))
+                };
+                if !valid {
+                    return Err(RuleFileError::new(
+                        line,
+                        format!("branch {} is not valid at level {}", branch, level),
+                    ));
+                }
+            }
+            levels.push(level);
+            branches.extend_from_slice(node_branches);
+        }
+
+        let (states, cells) = match (states, cells) {
+            (Some(states), Some(cells)) => (states, cells),
+            _ => {
+                return Err(RuleFileError::new(
+                    lines[0].0,
+                    "the tree needs num_states and num_neighbors",
+                ))
+            }
+        };
+        let last_line = lines[lines.len() - 1].0;
+        if expected_nodes.is_some_and(|nodes| nodes != levels.len()) {
+            return Err(RuleFileError::new(
+                last_line,
+                format!(
+                    "expected {} nodes, found {}",
+                    expected_nodes.unwrap_or(0),
+                    levels.len()
+                ),
+            ));
+        }
+        if levels.last() != Some(&cells.len()) {
+            return Err(RuleFileError::new(
+                last_line,
+                format!("the last node must be the root, at level {}", cells.len()),
+            ));
+        }
+
+        Ok((
+            states,
+            cells,
+            Tree {
+                states: states as usize,
+                branches,
+                root: levels.len() - 1,
+            },
+        ))
+    }
+
+    fn next_state(&self, cells: &[u8]) -> u8 {
+        let mut node = self.root;
+        for &state in cells {
+            node = self.branches[node * self.states + state as usize] as usize;
+        }
+        node as u8
+    }
+}
+
+/// Why a `.rule` file couldn't be loaded, with the line it went wrong on,
+/// counting from 1.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RuleFileError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl RuleFileError {
+    fn new(line: usize, message: impl Into<String>) -> RuleFileError {
+        RuleFileError {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for RuleFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for RuleFileError {}
+
+impl From<RuleFileError> for JsValue {
+    fn from(err: RuleFileError) -> JsValue {
+        JsValue::from_str(&err.to_string())
+    }
+}
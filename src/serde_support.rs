@@ -0,0 +1,149 @@
+//! `Serialize` and `Deserialize` for the types that don't derive them.
+//!
+//! Rules are stored as their rulestring. A Universe is stored as its size,
+//! rule, topology, generation count and its cells as the body of an RLE
+//! pattern, which stays small for sparse boards in text and binary formats
+//! alike. A loaded `.rule` file is stored as its text. A Pattern's rule and
+//! size are checked as it is read, so a Pattern taken from a Universe with a
+//! `.rule` file, which only has the file's name, can't be read back.
+
+use serde::de::{self, Deserializer};
+use serde::ser::Serializer;
+use serde::{Deserialize, Serialize};
+
+use crate::formats::{rle, Pattern};
+use crate::rule::Rule;
+use crate::topology::Topology;
+use crate::Universe;
+
+impl Serialize for Rule {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Rule {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Rule, D::Error> {
+        let rule = String::deserialize(deserializer)?;
+        Rule::parse(&rule).map_err(de::Error::custom)
+    }
+}
+
+/// A pattern's rulestring, checked along with any topology suffix so that
+/// the pattern can be turned into a Universe.
+fn deserialize_pattern_rule<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<String>, D::Error> {
+    let rule = Option::<String>::deserialize(deserializer)?;
+    if let Some(rule) = &rule {
+        let (parsed, suffix) = match rule.split_once(':') {
+            Some((rule, suffix)) => (rule, Some(suffix)),
+            None => (rule.as_str(), None),
+        };
+        Rule::parse(parsed).map_err(de::Error::custom)?;
+        suffix
+            .map(Topology::parse)
+            .transpose()
+            .map_err(de::Error::custom)?;
+    }
+    Ok(rule)
+}
+
+/// The stored form of a Pattern, checked before it becomes one.
+#[derive(Deserialize)]
+struct PatternData {
+    name: Option<String>,
+    author: Option<String>,
+    comments: Vec<String>,
+    #[serde(default, deserialize_with = "deserialize_pattern_rule")]
+    rule: Option<String>,
+    width: u32,
+    height: u32,
+    cells: Vec<(u32, u32, u8)>,
+}
+
+impl<'de> Deserialize<'de> for Pattern {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Pattern, D::Error> {
+        let data = PatternData::deserialize(deserializer)?;
+        if !Universe::fits(data.width, data.height) {
+            return Err(de::Error::custom(format!(
+                "a {}x{} pattern has too many cells",
+                data.width, data.height
+            )));
+        }
+
+        Ok(Pattern {
+            name: data.name,
+            author: data.author,
+            comments: data.comments,
+            rule: data.rule,
+            width: data.width,
+            height: data.height,
+            cells: data.cells,
+        })
+    }
+}
+
+/// The stored form of a Universe.
+#[derive(Serialize, Deserialize)]
+struct UniverseData {
+    width: u32,
+    height: u32,
+    rule: Rule,
+    topology: Topology,
+    generation: u64,
+    cells: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rule_file: Option<String>,
+}
+
+impl Serialize for Universe {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        UniverseData {
+            width: self.width,
+            height: self.height,
+            rule: self.rule,
+            topology: self.topology,
+            generation: self.generation,
+            cells: rle::write_cells(&Pattern::from_universe(self)),
+            rule_file: self
+                .get_rule_file()
+                .map(|rule_file| rule_file.source().to_string()),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Universe {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Universe, D::Error> {
+        let data = UniverseData::deserialize(deserializer)?;
+        let cells = rle::parse(&data.cells).map_err(de::Error::custom)?;
+        if cells.width > data.width || cells.height > data.height {
+            return Err(de::Error::custom(format!(
+                "the cells need a {}x{} Universe but it is {}x{}",
+                cells.width, cells.height, data.width, data.height
+            )));
+        }
+
+        if !Universe::fits(data.width, data.height) {
+            return Err(de::Error::custom(format!(
+                "a {}x{} Universe has too many cells",
+                data.width, data.height
+            )));
+        }
+
+        let mut universe = Universe::empty(data.width, data.height);
+        universe.rule = data.rule;
+        universe.topology = data.topology;
+        universe.generation = data.generation;
+        if let Some(rule_file) = &data.rule_file {
+            universe
+                .load_rule_file(rule_file)
+                .map_err(de::Error::custom)?;
+        } else if universe.is_multi_state() {
+            universe.refresh_states();
+        }
+        universe.load_cells(0, 0, &cells.cells);
+        Ok(universe)
+    }
+}
@@ -0,0 +1,165 @@
+use std::ops::{BitAnd, BitOr, BitXor, Not};
+
+use crate::kernel::Lanes;
+
+/// Two words stepped together in a 128 bit WebAssembly SIMD vector.
+///
+/// Only built when compiling for wasm32 with the `simd128` target feature,
+/// otherwise `Wide` is a pair of plain words with the same behaviour.
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+#[derive(Clone, Copy)]
+pub(crate) struct Wide(core::arch::wasm32::v128);
+
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+mod vector {
+    use core::arch::wasm32::*;
+
+    use super::*;
+
+    impl BitAnd for Wide {
+        type Output = Wide;
+
+        fn bitand(self, other: Wide) -> Wide {
+            Wide(v128_and(self.0, other.0))
+        }
+    }
+
+    impl BitOr for Wide {
+        type Output = Wide;
+
+        fn bitor(self, other: Wide) -> Wide {
+            Wide(v128_or(self.0, other.0))
+        }
+    }
+
+    impl BitXor for Wide {
+        type Output = Wide;
+
+        fn bitxor(self, other: Wide) -> Wide {
+            Wide(v128_xor(self.0, other.0))
+        }
+    }
+
+    impl Not for Wide {
+        type Output = Wide;
+
+        fn not(self) -> Wide {
+            Wide(v128_not(self.0))
+        }
+    }
+
+    impl Lanes for Wide {
+        const WORDS: usize = 2;
+
+        fn from_fn(mut word: impl FnMut(usize) -> u64) -> Wide {
+            Wide(u64x2(word(0), word(1)))
+        }
+
+        fn word(self, lane: usize) -> u64 {
+            match lane {
+                0 => u64x2_extract_lane::<0>(self.0),
+                _ => u64x2_extract_lane::<1>(self.0),
+            }
+        }
+    }
+}
+
+/// Two words stepped together, emulating the 128 bit vector used on
+/// WebAssembly with SIMD.
+#[cfg(not(all(target_arch = "wasm32", target_feature = "simd128")))]
+#[derive(Clone, Copy)]
+pub(crate) struct Wide([u64; 2]);
+
+#[cfg(not(all(target_arch = "wasm32", target_feature = "simd128")))]
+mod emulated {
+    use super::*;
+
+    impl BitAnd for Wide {
+        type Output = Wide;
+
+        fn bitand(self, other: Wide) -> Wide {
+            Wide([self.0[0] & other.0[0], self.0[1] & other.0[1]])
+        }
+    }
+
+    impl BitOr for Wide {
+        type Output = Wide;
+
+        fn bitor(self, other: Wide) -> Wide {
+            Wide([self.0[0] | other.0[0], self.0[1] | other.0[1]])
+        }
+    }
+
+    impl BitXor for Wide {
+        type Output = Wide;
+
+        fn bitxor(self, other: Wide) -> Wide {
+            Wide([self.0[0] ^ other.0[0], self.0[1] ^ other.0[1]])
+        }
+    }
+
+    impl Not for Wide {
+        type Output = Wide;
+
+        fn not(self) -> Wide {
+            Wide([!self.0[0], !self.0[1]])
+        }
+    }
+
+    impl Lanes for Wide {
+        const WORDS: usize = 2;
+
+        fn from_fn(mut word: impl FnMut(usize) -> u64) -> Wide {
+            Wide([word(0), word(1)])
+        }
+
+        fn word(self, lane: usize) -> u64 {
+            self.0[lane]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::Kernel;
+    use crate::rule::Rule;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::wasm_bindgen_test as test;
+
+    /// A xorshift generator, enough to give the kernels varied words.
+    fn words(seed: u64) -> impl Iterator<Item = u64> {
+        std::iter::successors(Some(seed), |&x| {
+            let x = x ^ (x << 13);
+            let x = x ^ (x >> 7);
+            Some(x ^ (x << 17))
+        })
+    }
+
+    #[test]
+    fn wide_kernel_matches_scalar() {
+        for rule in &["B3/S23", "B36/S23", "B2/S", "B2n3/S23-q"] {
+            let rule = Rule::parse(rule).unwrap();
+            let kernel = Kernel::new(&rule);
+            let mut words = words(0x9e37_79b9_7f4a_7c15);
+
+            for _ in 0..256 {
+                let neighbours: [[u64; 8]; 2] =
+                    [[0; 8]; 2].map(|lane| lane.map(|_| words.next().unwrap()));
+                let alive = [words.next().unwrap(), words.next().unwrap()];
+
+                let wide = kernel.next_lanes(
+                    &[0, 1, 2, 3, 4, 5, 6, 7].map(|n| Wide::from_fn(|lane| neighbours[lane][n])),
+                    Wide::from_fn(|lane| alive[lane]),
+                );
+                for lane in 0..2 {
+                    assert_eq!(
+                        wide.word(lane),
+                        kernel.next_word(&neighbours[lane], alive[lane])
+                    );
+                }
+            }
+        }
+    }
+}
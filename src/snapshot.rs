@@ -0,0 +1,263 @@
+//! A compact binary snapshot of a Universe, for saving a session to
+//! localStorage or disk and restoring it later.
+//!
+//! Numbers are little endian.
+//!
+//! | Bytes    | Contents                                                 |
+//! |----------|----------------------------------------------------------|
+//! | 4        | The magic bytes `LIFE`                                   |
+//! | 1        | The format version, currently 1                          |
+//! | 4, 4     | Width and height                                         |
+//! | 8        | Generation count                                         |
+//! | 1        | Topology, numbered in the order of `TOPOLOGIES`          |
+//! | 4, n     | Length of the rule, then the rule                        |
+//! | 4, n     | Length of the cells, then the cells DEFLATE compressed   |
+//! | 4        | CRC-32 of everything before it                           |
+//!
+//! The rule is a rulestring, or the whole text of a Golly `.rule` file.
+//!
+//! For two-state rules the cells are the `FixedBitSet` blocks, four bytes
+//! each. For rules with more states they are one byte per cell.
+//!
+//! Share strings are snapshots in URL-safe base64 without padding, so they
+//! can go straight into a link.
+
+use std::convert::TryInto;
+use std::fmt;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use fixedbitset::FixedBitSet;
+use wasm_bindgen::prelude::*;
+
+use crate::rule::RuleError;
+use crate::topology::Topology;
+use crate::Universe;
+
+const MAGIC: &[u8; 4] = b"LIFE";
+const VERSION: u8 = 1;
+
+/// Topologies in the order they're numbered in snapshots. New topologies
+/// must be added to the end.
+const TOPOLOGIES: [Topology; 7] = [
+    Topology::Torus,
+    Topology::Plane,
+    Topology::HorizontalCylinder,
+    Topology::VerticalCylinder,
+    Topology::KleinBottle,
+    Topology::CrossSurface,
+    Topology::Sphere,
+];
+
+pub(crate) fn serialize(universe: &Universe) -> Vec<u8> {
+    let cells: Vec<u8> = if universe.is_multi_state() {
+        universe.states.clone()
+    } else {
+        universe
+            .cells
+            .as_slice()
+            .iter()
+            .flat_map(|block| block.to_le_bytes())
+            .collect()
+    };
+    let cells = miniz_oxide::deflate::compress_to_vec(&cells, 6);
+    let rule = match universe.get_rule_file() {
+        Some(rule_file) => rule_file.source().to_string(),
+        None => universe.rule.to_string(),
+    };
+    let topology = TOPOLOGIES
+        .iter()
+        .position(|&topology| topology == universe.topology)
+        .unwrap_or(0);
+
+    let mut bytes = MAGIC.to_vec();
+    bytes.push(VERSION);
+    bytes.extend(universe.width.to_le_bytes());
+    bytes.extend(universe.height.to_le_bytes());
+    bytes.extend(universe.generation.to_le_bytes());
+    bytes.push(topology as u8);
+    bytes.extend((rule.len() as u32).to_le_bytes());
+    bytes.extend(rule.as_bytes());
+    bytes.extend((cells.len() as u32).to_le_bytes());
+    bytes.extend(cells);
+    bytes.extend(crc32fast::hash(&bytes).to_le_bytes());
+    bytes
+}
+
+pub(crate) fn deserialize(bytes: &[u8]) -> Result<Universe, SnapshotError> {
+    let mut reader = Reader(bytes);
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err(SnapshotError::NotASnapshot);
+    }
+    let version = reader.take(1)?[0];
+    if version != VERSION {
+        return Err(SnapshotError::UnsupportedVersion(version));
+    }
+
+    let (body, checksum) = bytes.split_at(bytes.len().saturating_sub(4));
+    if checksum.len() < 4 || crc32fast::hash(body).to_le_bytes() != checksum {
+        return Err(SnapshotError::Checksum);
+    }
+    let mut reader = Reader(&body[MAGIC.len() + 1..]);
+
+    let width = u32::from_le_bytes(reader.array()?);
+    let height = u32::from_le_bytes(reader.array()?);
+    let generation = u64::from_le_bytes(reader.array()?);
+    let topology = reader.take(1)?[0];
+    let topology = *TOPOLOGIES
+        .get(topology as usize)
+        .ok_or(SnapshotError::UnknownTopology(topology))?;
+    let rule_length = u32::from_le_bytes(reader.array()?) as usize;
+    let rule = std::str::from_utf8(reader.take(rule_length)?)
+        .map_err(|_| SnapshotError::Corrupt("the rule isn't valid UTF-8".to_string()))?;
+    let cells_length = u32::from_le_bytes(reader.array()?);
+    let cells = reader.take(cells_length as usize)?;
+    if !reader.0.is_empty() {
+        return Err(SnapshotError::Corrupt(
+            "there are bytes after the cells".to_string(),
+        ));
+    }
+
+    if !Universe::fits(width, height) {
+        return Err(SnapshotError::Corrupt(format!(
+            "a {}x{} Universe has too many cells",
+            width, height
+        )));
+    }
+
+    let mut universe = Universe::empty(0, 0);
+    if rule.starts_with("@RULE") {
+        universe.load_rule_file(rule).map_err(|error| {
+            SnapshotError::Corrupt(format!("the rule file is invalid: {}", error))
+        })?;
+    } else {
+        universe.set_rule(rule).map_err(SnapshotError::Rule)?;
+    }
+    let count = width as usize * height as usize;
+    let expected = if universe.is_multi_state() {
+        count
+    } else {
+        count.div_ceil(32) * 4
+    };
+
+    let cells = miniz_oxide::inflate::decompress_to_vec_with_limit(cells, expected)
+        .map_err(|_| SnapshotError::Corrupt("the cells couldn't be decompressed".to_string()))?;
+    if cells.len() != expected {
+        return Err(SnapshotError::Corrupt(format!(
+            "expected {} bytes of cells, found {}",
+            expected,
+            cells.len()
+        )));
+    }
+
+    universe.width = width;
+    universe.height = height;
+    universe.topology = topology;
+    universe.generation = generation;
+    if universe.is_multi_state() {
+        let states = universe.state_count();
+        if let Some(&state) = cells.iter().find(|&&state| state >= states) {
+            return Err(SnapshotError::Corrupt(format!(
+                "a cell is in state {} of a {} state rule",
+                state, states
+            )));
+        }
+        universe.cells = FixedBitSet::with_capacity(count);
+        for (idx, &state) in cells.iter().enumerate() {
+            universe.cells.set(idx, state == 1);
+        }
+        universe.states = cells;
+    } else {
+        let mut blocks: Vec<u32> = cells
+            .chunks_exact(4)
+            .map(|block| u32::from_le_bytes([block[0], block[1], block[2], block[3]]))
+            .collect();
+        // Bits past the last cell are always clear.
+        if let (Some(last), 1..=31) = (blocks.last_mut(), count % 32) {
+            *last &= (1 << (count % 32)) - 1;
+        }
+        universe.cells = FixedBitSet::with_capacity_and_blocks(count, blocks);
+    }
+    universe.mark_all_changed();
+
+    Ok(universe)
+}
+
+pub(crate) fn to_share_string(universe: &Universe) -> String {
+    URL_SAFE_NO_PAD.encode(serialize(universe))
+}
+
+pub(crate) fn from_share_string(text: &str) -> Result<Universe, SnapshotError> {
+    let bytes = URL_SAFE_NO_PAD
+        .decode(text.trim())
+        .map_err(|error| SnapshotError::Encoding(error.to_string()))?;
+    deserialize(&bytes)
+}
+
+/// Reads fields from the front of a snapshot.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], SnapshotError> {
+        if self.0.len() < length {
+            return Err(SnapshotError::Truncated);
+        }
+        let (field, rest) = self.0.split_at(length);
+        self.0 = rest;
+        Ok(field)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], SnapshotError> {
+        Ok(self.take(N)?.try_into().expect("took N bytes"))
+    }
+}
+
+/// Why a snapshot couldn't be restored.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SnapshotError {
+    /// The data doesn't start with the snapshot magic bytes.
+    NotASnapshot,
+    /// The snapshot was written by a newer version of the format.
+    UnsupportedVersion(u8),
+    /// The snapshot ends part way through.
+    Truncated,
+    /// The checksum doesn't match, so the snapshot was damaged.
+    Checksum,
+    /// The topology number isn't one this version knows.
+    UnknownTopology(u8),
+    /// The rule couldn't be parsed.
+    Rule(RuleError),
+    /// The checksum matched but the contents don't make sense.
+    Corrupt(String),
+    /// A share string isn't valid URL-safe base64.
+    Encoding(String),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SnapshotError::NotASnapshot => write!(f, "not a Universe snapshot"),
+            SnapshotError::UnsupportedVersion(version) => {
+                write!(f, "snapshot version {} is not supported", version)
+            }
+            SnapshotError::Truncated => write!(f, "the snapshot is truncated"),
+            SnapshotError::Checksum => write!(f, "the snapshot checksum doesn't match"),
+            SnapshotError::UnknownTopology(topology) => {
+                write!(f, "topology {} is not recognised", topology)
+            }
+            SnapshotError::Rule(error) => write!(f, "{}", error),
+            SnapshotError::Corrupt(message) => write!(f, "the snapshot is corrupt: {}", message),
+            SnapshotError::Encoding(message) => {
+                write!(f, "the share string is malformed: {}", message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl From<SnapshotError> for JsValue {
+    fn from(err: SnapshotError) -> JsValue {
+        JsValue::from_str(&err.to_string())
+    }
+}
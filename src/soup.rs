@@ -0,0 +1,285 @@
+//! Random soups with enforced symmetry, as apgsearch generates them for a
+//! census.
+//!
+//! A soup fills a rectangle. Cells are worked on in doubled coordinates
+//! measured from the centre of the rectangle, so the centre can lie on a
+//! cell, on the middle of an edge or on a corner, and every symmetry maps
+//! the rectangle onto itself.
+
+use std::fmt;
+
+use wasm_bindgen::prelude::*;
+
+use crate::platform::Random;
+
+/// The symmetries apgsearch searches, named after their apgsearch codes.
+///
+/// The digit after the underscore says where the centre lies: `1` on a
+/// cell, `2` on the middle of an edge and `4` on a corner. Rotations and
+/// diagonal reflections need a square soup.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Symmetry {
+    /// No symmetry.
+    C1,
+    /// Rotating by 180 degrees about a cell.
+    C2_1,
+    /// Rotating by 180 degrees about the middle of a horizontal edge.
+    C2_2,
+    /// Rotating by 180 degrees about a corner.
+    C2_4,
+    /// Rotating by 90 degrees about a cell.
+    C4_1,
+    /// Rotating by 90 degrees about a corner.
+    C4_4,
+    /// `D2_+1`, reflecting top to bottom across a row of cells.
+    D2Plus1,
+    /// `D2_+2`, reflecting top to bottom between two rows.
+    D2Plus2,
+    /// `D2_x`, reflecting across the leading diagonal.
+    D2X,
+    /// `D4_+1`, reflecting top to bottom and left to right across cells.
+    D4Plus1,
+    /// `D4_+2`, reflecting left to right across a column and top to bottom
+    /// between two rows.
+    D4Plus2,
+    /// `D4_+4`, reflecting top to bottom and left to right between cells.
+    D4Plus4,
+    /// `D4_x1`, reflecting across both diagonals, which cross on a cell.
+    D4X1,
+    /// `D4_x4`, reflecting across both diagonals, which cross on a corner.
+    D4X4,
+    /// Every rotation and reflection about a cell.
+    D8_1,
+    /// Every rotation and reflection about a corner.
+    D8_4,
+}
+
+/// A map of doubled coordinates, `(x, y)` to the image of the cell.
+type Transform = fn(i64, i64) -> (i64, i64);
+
+const IDENTITY: Transform = |x, y| (x, y);
+const ROTATE_90: Transform = |x, y| (-y, x);
+const ROTATE_180: Transform = |x, y| (-x, -y);
+const ROTATE_270: Transform = |x, y| (y, -x);
+const FLIP_ROWS: Transform = |x, y| (x, -y);
+const FLIP_COLUMNS: Transform = |x, y| (-x, y);
+const DIAGONAL: Transform = |x, y| (y, x);
+const ANTI_DIAGONAL: Transform = |x, y| (-y, -x);
+
+const C1: &[Transform] = &[IDENTITY];
+const C2: &[Transform] = &[IDENTITY, ROTATE_180];
+const C4: &[Transform] = &[IDENTITY, ROTATE_90, ROTATE_180, ROTATE_270];
+const D2_PLUS: &[Transform] = &[IDENTITY, FLIP_ROWS];
+const D2_X: &[Transform] = &[IDENTITY, DIAGONAL];
+const D4_PLUS: &[Transform] = &[IDENTITY, FLIP_ROWS, FLIP_COLUMNS, ROTATE_180];
+const D4_X: &[Transform] = &[IDENTITY, DIAGONAL, ANTI_DIAGONAL, ROTATE_180];
+const D8: &[Transform] = &[
+    IDENTITY,
+    ROTATE_90,
+    ROTATE_180,
+    ROTATE_270,
+    FLIP_ROWS,
+    FLIP_COLUMNS,
+    DIAGONAL,
+    ANTI_DIAGONAL,
+];
+
+/// Every symmetry with its apgsearch code.
+const SYMMETRIES: [(Symmetry, &str); 16] = [
+    (Symmetry::C1, "C1"),
+    (Symmetry::C2_1, "C2_1"),
+    (Symmetry::C2_2, "C2_2"),
+    (Symmetry::C2_4, "C2_4"),
+    (Symmetry::C4_1, "C4_1"),
+    (Symmetry::C4_4, "C4_4"),
+    (Symmetry::D2Plus1, "D2_+1"),
+    (Symmetry::D2Plus2, "D2_+2"),
+    (Symmetry::D2X, "D2_x"),
+    (Symmetry::D4Plus1, "D4_+1"),
+    (Symmetry::D4Plus2, "D4_+2"),
+    (Symmetry::D4Plus4, "D4_+4"),
+    (Symmetry::D4X1, "D4_x1"),
+    (Symmetry::D4X4, "D4_x4"),
+    (Symmetry::D8_1, "D8_1"),
+    (Symmetry::D8_4, "D8_4"),
+];
+
+/// Whether the width and height of a soup must be odd, for a centre on a
+/// cell, or even, for a centre between cells. `None` allows either.
+type Parity = (Option<bool>, Option<bool>);
+
+const ANY: Parity = (None, None);
+const CELL: Parity = (Some(true), Some(true));
+const EDGE: Parity = (Some(true), Some(false));
+const CORNER: Parity = (Some(false), Some(false));
+
+impl Symmetry {
+    /// Parse an apgsearch symmetry code such as `C2_4`, `D4_+1` or `D8_1`.
+    ///
+    /// `D2_+`, `D4_+` and `D4_x` without a digit are reflections across
+    /// cells, the same as `D2_+1`, `D4_+1` and `D4_x1`.
+    pub fn parse(code: &str) -> Result<Symmetry, SoupError> {
+        let code = code.trim();
+        let code = match code {
+            "D2_+" => "D2_+1",
+            "D4_+" => "D4_+1",
+            "D4_x" => "D4_x1",
+            code => code,
+        };
+
+        SYMMETRIES
+            .iter()
+            .find(|(_, name)| *name == code)
+            .map(|&(symmetry, _)| symmetry)
+            .ok_or_else(|| SoupError::UnknownSymmetry(code.to_string()))
+    }
+
+    /// The apgsearch code for the symmetry.
+    pub fn code(self) -> &'static str {
+        SYMMETRIES
+            .iter()
+            .find(|&&(symmetry, _)| symmetry == self)
+            .map_or("C1", |&(_, name)| name)
+    }
+
+    fn group(self) -> &'static [Transform] {
+        match self {
+            Symmetry::C1 => C1,
+            Symmetry::C2_1 | Symmetry::C2_2 | Symmetry::C2_4 => C2,
+            Symmetry::C4_1 | Symmetry::C4_4 => C4,
+            Symmetry::D2Plus1 | Symmetry::D2Plus2 => D2_PLUS,
+            Symmetry::D2X => D2_X,
+            Symmetry::D4Plus1 | Symmetry::D4Plus2 | Symmetry::D4Plus4 => D4_PLUS,
+            Symmetry::D4X1 | Symmetry::D4X4 => D4_X,
+            Symmetry::D8_1 | Symmetry::D8_4 => D8,
+        }
+    }
+
+    /// Whether the symmetry swaps rows and columns.
+    fn is_square(self) -> bool {
+        matches!(
+            self,
+            Symmetry::C4_1
+                | Symmetry::C4_4
+                | Symmetry::D2X
+                | Symmetry::D4X1
+                | Symmetry::D4X4
+                | Symmetry::D8_1
+                | Symmetry::D8_4
+        )
+    }
+
+    fn parity(self) -> Parity {
+        match self {
+            Symmetry::C1 | Symmetry::D2X => ANY,
+            Symmetry::C2_1
+            | Symmetry::C4_1
+            | Symmetry::D4Plus1
+            | Symmetry::D4X1
+            | Symmetry::D8_1 => CELL,
+            Symmetry::C2_2 | Symmetry::D4Plus2 => EDGE,
+            Symmetry::C2_4
+            | Symmetry::C4_4
+            | Symmetry::D4Plus4
+            | Symmetry::D4X4
+            | Symmetry::D8_4 => CORNER,
+            Symmetry::D2Plus1 => (None, Some(true)),
+            Symmetry::D2Plus2 => (None, Some(false)),
+        }
+    }
+
+    /// The largest soup with this symmetry that fits in a rectangle, as
+    /// its width and height.
+    ///
+    /// Rotations and diagonal reflections use a square as wide as the
+    /// shorter side, and a side one cell too long for where the centre has
+    /// to lie is shortened by one.
+    pub fn fit(self, width: u32, height: u32) -> (u32, u32) {
+        let (width, height) = if self.is_square() {
+            (width.min(height), width.min(height))
+        } else {
+            (width, height)
+        };
+
+        let fit = |size: u32, odd: Option<bool>| match odd {
+            Some(odd) if size > 0 && (size % 2 == 1) != odd => size - 1,
+            _ => size,
+        };
+        let (odd_width, odd_height) = self.parity();
+        (fit(width, odd_width), fit(height, odd_height))
+    }
+}
+
+impl fmt::Display for Symmetry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+/// A random soup with the symmetry, as rows of cells. The width and height
+/// must already fit the symmetry.
+///
+/// One random choice is made for each set of cells the symmetry maps onto
+/// each other, so every cell is alive with the given density.
+pub(crate) fn generate(
+    width: u32,
+    height: u32,
+    symmetry: Symmetry,
+    density: f64,
+    random: &mut impl Random,
+) -> Vec<bool> {
+    let (width, height) = (width as i64, height as i64);
+    let mut cells = vec![false; (width * height) as usize];
+
+    for row in 0..height {
+        for col in 0..width {
+            let (x, y) = (2 * col - (width - 1), 2 * row - (height - 1));
+            // The first cell of each set in reading order makes the choice
+            // for the rest.
+            let first = symmetry
+                .group()
+                .iter()
+                .map(|transform| {
+                    let (x, y) = transform(x, y);
+                    ((y + height - 1) / 2) * width + (x + width - 1) / 2
+                })
+                .min()
+                .unwrap_or(0) as usize;
+
+            let idx = (row * width + col) as usize;
+            cells[idx] = if first == idx {
+                random.chance(density)
+            } else {
+                cells[first]
+            };
+        }
+    }
+
+    cells
+}
+
+/// Why a soup couldn't be made.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SoupError {
+    /// The symmetry code isn't one apgsearch uses.
+    UnknownSymmetry(String),
+}
+
+impl fmt::Display for SoupError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SoupError::UnknownSymmetry(code) => {
+                write!(f, "{} is not a recognised symmetry", code)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SoupError {}
+
+impl From<SoupError> for JsValue {
+    fn from(err: SoupError) -> JsValue {
+        JsValue::from_str(&err.to_string())
+    }
+}
@@ -0,0 +1,291 @@
+use std::collections::{HashMap, HashSet};
+
+use wasm_bindgen::prelude::*;
+
+use crate::kernel::Kernel;
+use crate::rule::{Rule, RuleError};
+
+/// The width and height of a tile in cells.
+pub const TILE_SIZE: i64 = 64;
+
+/// A 64 by 64 block of cells, one row per word with column `c` in bit `c`.
+type Tile = [u64; TILE_SIZE as usize];
+
+/// The smallest rectangle containing every live cell, with inclusive
+/// bounds.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BoundingBox {
+    pub top: i64,
+    pub left: i64,
+    pub bottom: i64,
+    pub right: i64,
+}
+
+/// A Universe on an unbounded plane.
+///
+/// Live cells are kept in 64 by 64 tiles stored in a hash map, so only the
+/// regions with activity take up memory or time. Tiles are added as
+/// patterns grow into them and dropped once they empty out.
+///
+/// Only two-state rules on the immediate neighbourhood are supported, and
+/// not those where cells are born with no live neighbours.
+#[wasm_bindgen]
+pub struct SparseUniverse {
+    tiles: HashMap<(i64, i64), Tile>,
+    rule: Rule,
+    generation: u64,
+}
+
+impl SparseUniverse {
+    /// Set cells to be alive by passing the row and column of each cell as
+    /// an array.
+    pub fn set_cells(&mut self, cells: &[(i64, i64)]) {
+        for (row, col) in cells.iter().cloned() {
+            let (key, local_row, bit) = Self::locate(row, col);
+            self.tiles.entry(key).or_insert([0; TILE_SIZE as usize])[local_row] |= bit;
+        }
+    }
+
+    /// Every live cell as a row and column, in no particular order.
+    pub fn live_cells(&self) -> Vec<(i64, i64)> {
+        let mut cells = Vec::new();
+        for (&(tile_row, tile_col), tile) in &self.tiles {
+            for (local_row, &word) in tile.iter().enumerate() {
+                let mut word = word;
+                while word != 0 {
+                    let local_col = word.trailing_zeros() as i64;
+                    cells.push((
+                        tile_row * TILE_SIZE + local_row as i64,
+                        tile_col * TILE_SIZE + local_col,
+                    ));
+                    word &= word - 1;
+                }
+            }
+        }
+        cells
+    }
+
+    /// Get the rule the Universe is evolving under.
+    pub fn get_rule(&self) -> &Rule {
+        &self.rule
+    }
+
+    /// The number of tiles currently allocated.
+    pub fn tile_count(&self) -> usize {
+        self.tiles.len()
+    }
+
+    /// The tile holding a cell, the row within it and the cell's bit.
+    fn locate(row: i64, col: i64) -> ((i64, i64), usize, u64) {
+        let key = (row.div_euclid(TILE_SIZE), col.div_euclid(TILE_SIZE));
+        let local_row = row.rem_euclid(TILE_SIZE) as usize;
+        (key, local_row, 1 << col.rem_euclid(TILE_SIZE))
+    }
+
+    /// The tiles that may have live cells next generation: every occupied
+    /// tile, plus any neighbouring tile that live cells on its border could
+    /// spill into.
+    fn active_tiles(&self) -> HashSet<(i64, i64)> {
+        let last = TILE_SIZE as usize - 1;
+        let mut active = HashSet::with_capacity(self.tiles.len() * 2);
+
+        for (&(tile_row, tile_col), tile) in &self.tiles {
+            let top = tile[0];
+            let bottom = tile[last];
+            let left = tile.iter().any(|&word| word & 1 != 0);
+            let right = tile.iter().any(|&word| word >> last != 0);
+
+            let neighbours = [
+                (-1, 0, top != 0),
+                (1, 0, bottom != 0),
+                (0, -1, left),
+                (0, 1, right),
+                (-1, -1, top & 1 != 0),
+                (-1, 1, top >> last != 0),
+                (1, -1, bottom & 1 != 0),
+                (1, 1, bottom >> last != 0),
+            ];
+
+            active.insert((tile_row, tile_col));
+            for (delta_row, delta_col, spills) in neighbours.iter().cloned() {
+                if spills {
+                    active.insert((tile_row + delta_row, tile_col + delta_col));
+                }
+            }
+        }
+
+        active
+    }
+
+    /// Compute the next generation of one tile.
+    fn next_tile(&self, kernel: &Kernel, (tile_row, tile_col): (i64, i64)) -> Tile {
+        let mut around = [None; 9];
+        for (i, tile) in around.iter_mut().enumerate() {
+            let (delta_row, delta_col) = (i as i64 / 3 - 1, i as i64 % 3 - 1);
+            *tile = self
+                .tiles
+                .get(&(tile_row + delta_row, tile_col + delta_col));
+        }
+
+        // A row of the tile extended one cell either side, for rows -1 to 64
+        // reaching into the tiles above and below.
+        let extended_row = |row: i64| -> (u64, u64, u64) {
+            let band = (row.div_euclid(TILE_SIZE) + 1) as usize * 3;
+            let local_row = row.rem_euclid(TILE_SIZE) as usize;
+            let word = |tile: Option<&Tile>| tile.map_or(0, |tile| tile[local_row]);
+            (
+                word(around[band]) >> (TILE_SIZE - 1),
+                word(around[band + 1]),
+                word(around[band + 2]) & 1,
+            )
+        };
+
+        let mut next = [0; TILE_SIZE as usize];
+        for (row, word) in next.iter_mut().enumerate() {
+            let row = row as i64;
+            let (above_west, above, above_east) = extended_row(row - 1);
+            let (west, alive, east) = extended_row(row);
+            let (below_west, below, below_east) = extended_row(row + 1);
+
+            // Shifting right brings the neighbour to the east of each cell
+            // into its bit, and shifting left the neighbour to the west.
+            let neighbours = [
+                above,
+                (above >> 1) | (above_east << 63),
+                (alive >> 1) | (east << 63),
+                (below >> 1) | (below_east << 63),
+                below,
+                (below << 1) | below_west,
+                (alive << 1) | west,
+                (above << 1) | above_west,
+            ];
+
+            *word = kernel.next_word(&neighbours, alive);
+        }
+
+        next
+    }
+}
+
+impl Default for SparseUniverse {
+    fn default() -> SparseUniverse {
+        SparseUniverse::new()
+    }
+}
+
+// Public methods, exported to JavaScript.
+#[wasm_bindgen]
+impl SparseUniverse {
+    pub fn new() -> SparseUniverse {
+        SparseUniverse {
+            tiles: HashMap::new(),
+            rule: Rule::default(),
+            generation: 0,
+        }
+    }
+
+    /// Set the rule the Universe evolves under.
+    ///
+    /// Rules with more than two states, an extended neighbourhood or births
+    /// from empty neighbourhoods are rejected.
+    pub fn set_rule(&mut self, rule: &str) -> Result<(), RuleError> {
+        let parsed = Rule::parse(rule)?;
+        if parsed.states() > 2 || parsed.radius() > 1 || parsed.births_from_nothing() {
+            return Err(RuleError::Unsupported(rule.to_string()));
+        }
+
+        self.rule = parsed;
+        Ok(())
+    }
+
+    /// The current rule in `B/S` notation.
+    pub fn rule(&self) -> String {
+        self.rule.to_string()
+    }
+
+    pub fn tick(&mut self) {
+        let kernel = Kernel::new(&self.rule);
+        let mut next = HashMap::with_capacity(self.tiles.len());
+
+        for key in self.active_tiles() {
+            let tile = self.next_tile(&kernel, key);
+            if tile.iter().any(|&word| word != 0) {
+                next.insert(key, tile);
+            }
+        }
+
+        self.tiles = next;
+        self.generation += 1;
+    }
+
+    /// Toggles the state of a cell
+    pub fn toggle(&mut self, row: i64, col: i64) {
+        let (key, local_row, bit) = Self::locate(row, col);
+        let tile = self.tiles.entry(key).or_insert([0; TILE_SIZE as usize]);
+        tile[local_row] ^= bit;
+
+        if tile.iter().all(|&word| word == 0) {
+            self.tiles.remove(&key);
+        }
+    }
+
+    pub fn is_alive(&self, row: i64, col: i64) -> bool {
+        let (key, local_row, bit) = Self::locate(row, col);
+        self.tiles
+            .get(&key)
+            .is_some_and(|tile| tile[local_row] & bit != 0)
+    }
+
+    pub fn clear(&mut self) {
+        self.tiles.clear();
+        self.generation = 0;
+    }
+
+    /// The number of live cells.
+    pub fn population(&self) -> u64 {
+        self.tiles
+            .values()
+            .flat_map(|tile| tile.iter())
+            .map(|word| word.count_ones() as u64)
+            .sum()
+    }
+
+    /// How many times the Universe has ticked.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// The smallest rectangle containing every live cell, or nothing if
+    /// every cell is dead.
+    pub fn bounding_box(&self) -> Option<BoundingBox> {
+        let mut bounds: Option<BoundingBox> = None;
+
+        for (&(tile_row, tile_col), tile) in &self.tiles {
+            let columns = tile.iter().fold(0, |columns, &word| columns | word);
+            let first_row = tile.iter().position(|&word| word != 0);
+            let last_row = tile.iter().rposition(|&word| word != 0);
+
+            if let (Some(first_row), Some(last_row)) = (first_row, last_row) {
+                let tile = BoundingBox {
+                    top: tile_row * TILE_SIZE + first_row as i64,
+                    left: tile_col * TILE_SIZE + columns.trailing_zeros() as i64,
+                    bottom: tile_row * TILE_SIZE + last_row as i64,
+                    right: tile_col * TILE_SIZE + 63 - columns.leading_zeros() as i64,
+                };
+
+                bounds = Some(match bounds {
+                    Some(bounds) => BoundingBox {
+                        top: bounds.top.min(tile.top),
+                        left: bounds.left.min(tile.left),
+                        bottom: bounds.bottom.max(tile.bottom),
+                        right: bounds.right.max(tile.right),
+                    },
+                    None => tile,
+                });
+            }
+        }
+
+        bounds
+    }
+}
@@ -0,0 +1,194 @@
+use std::fmt;
+
+use wasm_bindgen::prelude::*;
+
+use crate::rule::RuleError;
+
+/// How the edges of a `Universe` are joined together.
+///
+/// These mirror the bounded grids Golly describes with a rulestring suffix,
+/// such as `B3/S23:K64*,32` for a 64 by 32 Klein bottle.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Topology {
+    /// Both pairs of edges are joined, so patterns wrap all the way round.
+    #[default]
+    Torus,
+    /// Nothing is joined and everything beyond the edges is dead.
+    Plane,
+    /// The left and right edges are joined, the top and bottom are dead.
+    HorizontalCylinder,
+    /// The top and bottom edges are joined, the left and right are dead.
+    VerticalCylinder,
+    /// The left and right edges are joined, the top and bottom are joined
+    /// with a twist so patterns come back mirrored left to right.
+    KleinBottle,
+    /// Both pairs of edges are joined with a twist.
+    CrossSurface,
+    /// The top edge is joined to the left edge and the bottom edge to the
+    /// right. Only meaningful for square grids.
+    Sphere,
+}
+
+/// A parsed topology suffix, with the grid size if one was given.
+///
+/// A width or height of `None` leaves that dimension as it is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Bounds {
+    pub topology: Topology,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+impl Topology {
+    /// Parse a Golly topology suffix, with or without the leading `:`, such
+    /// as `T64,32`, `P100,100`, `K64*,32` or `S50`.
+    ///
+    /// A torus with a zero width or height is a cylinder, and a single size
+    /// means a square grid.
+    pub fn parse(suffix: &str) -> Result<Bounds, RuleError> {
+        let suffix = suffix.trim();
+        let suffix = suffix.strip_prefix(':').unwrap_or(suffix);
+        let invalid = || RuleError::InvalidTopology(suffix.to_string());
+
+        let mut chars = suffix.chars();
+        let kind = chars.next().ok_or_else(invalid)?.to_ascii_uppercase();
+        let size = chars.as_str();
+
+        let (width, height) = if size.is_empty() {
+            (None, None)
+        } else {
+            let (width, height) = size.split_once(',').unwrap_or((size, size));
+            // Golly marks the twisted edges of a Klein bottle with a `*`,
+            // and needs it to know which pair is twisted. Only a twist on
+            // the top and bottom edges is supported.
+            let width = match (kind, width.strip_suffix('*')) {
+                ('K', Some(width)) => width,
+                ('K', None) => return Err(invalid()),
+                _ => width,
+            };
+            let parse = |dimension: &str| dimension.parse::<u32>().map_err(|_| invalid());
+            (Some(parse(width)?), Some(parse(height)?))
+        };
+
+        let topology = match (kind, width, height) {
+            ('T', Some(0), Some(0)) => return Err(invalid()),
+            ('T', _, Some(0)) => Topology::HorizontalCylinder,
+            ('T', Some(0), _) => Topology::VerticalCylinder,
+            ('T', _, _) => Topology::Torus,
+            ('P', _, _) => Topology::Plane,
+            ('K', _, _) => Topology::KleinBottle,
+            ('C', _, _) => Topology::CrossSurface,
+            ('S', width, height) if width == height => Topology::Sphere,
+            _ => return Err(invalid()),
+        };
+
+        // Unbounded dimensions are left at the current size.
+        let bounded = |dimension: Option<u32>| dimension.filter(|&size| size > 0);
+        match (topology, bounded(width), bounded(height)) {
+            (Topology::Torus, ..)
+            | (Topology::HorizontalCylinder, ..)
+            | (Topology::VerticalCylinder, ..) => (),
+            (_, None, Some(_)) | (_, Some(_), None) => return Err(invalid()),
+            _ => (),
+        }
+
+        Ok(Bounds {
+            topology,
+            width: bounded(width),
+            height: bounded(height),
+        })
+    }
+
+    /// The Golly suffix describing this topology on a grid of the given
+    /// size, empty for the default torus.
+    pub fn suffix(&self, width: u32, height: u32) -> String {
+        match self {
+            Topology::Torus => String::new(),
+            Topology::Plane => format!(":P{},{}", width, height),
+            Topology::HorizontalCylinder => format!(":T{},0", width),
+            Topology::VerticalCylinder => format!(":T0,{}", height),
+            Topology::KleinBottle => format!(":K{}*,{}", width, height),
+            Topology::CrossSurface => format!(":C{},{}", width, height),
+            Topology::Sphere => format!(":S{}", width),
+        }
+    }
+
+    /// Find the cell on a `width` by `height` grid that the possibly out of
+    /// range `row` and `col` refer to, or `None` if it lies beyond a dead
+    /// edge. A grid with no rows or columns has no cells at all.
+    pub fn map(&self, row: i64, col: i64, width: u32, height: u32) -> Option<(u32, u32)> {
+        if width == 0 || height == 0 {
+            return None;
+        }
+        let (width, height) = (width as i64, height as i64);
+        let in_rows = (0..height).contains(&row);
+        let in_cols = (0..width).contains(&col);
+        if in_rows && in_cols {
+            return Some((row as u32, col as u32));
+        }
+
+        // How many times each pair of edges is crossed decides whether the
+        // other coordinate is mirrored on a twisted surface.
+        let wrapped_row = row.rem_euclid(height);
+        let wrapped_col = col.rem_euclid(width);
+        let row_twists = row.div_euclid(height) % 2 != 0;
+        let col_twists = col.div_euclid(width) % 2 != 0;
+
+        let (row, col) = match self {
+            Topology::Torus => (wrapped_row, wrapped_col),
+            Topology::Plane => return None,
+            Topology::HorizontalCylinder if in_rows => (row, wrapped_col),
+            Topology::VerticalCylinder if in_cols => (wrapped_row, col),
+            Topology::HorizontalCylinder | Topology::VerticalCylinder => return None,
+            Topology::KleinBottle if row_twists => (wrapped_row, width - 1 - wrapped_col),
+            Topology::KleinBottle => (wrapped_row, wrapped_col),
+            Topology::CrossSurface => (
+                if col_twists {
+                    height - 1 - wrapped_row
+                } else {
+                    wrapped_row
+                },
+                if row_twists {
+                    width - 1 - wrapped_col
+                } else {
+                    wrapped_col
+                },
+            ),
+            Topology::Sphere => {
+                // Fold across the corner joining each pair of edges, so the
+                // cell above (0, c) is (c, 0) and the cell right of
+                // (r, width - 1) is (height - 1, r).
+                match (in_rows, in_cols) {
+                    (false, true) if row < 0 => (col, -row - 1),
+                    (false, true) => (col, width - (row - height) - 1),
+                    (true, false) if col < 0 => (-col - 1, row),
+                    (true, false) => (height - (col - width) - 1, row),
+                    _ => return None,
+                }
+            }
+        };
+
+        if (0..height).contains(&row) && (0..width).contains(&col) {
+            Some((row as u32, col as u32))
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for Topology {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Topology::Torus => "torus",
+            Topology::Plane => "plane",
+            Topology::HorizontalCylinder => "horizontal cylinder",
+            Topology::VerticalCylinder => "vertical cylinder",
+            Topology::KleinBottle => "Klein bottle",
+            Topology::CrossSurface => "cross-surface",
+            Topology::Sphere => "sphere",
+        };
+        write!(f, "{}", name)
+    }
+}
@@ -1,9 +1,7 @@
-extern crate web_sys;
-
 #[macro_export]
 macro_rules! log {
     ( $( $t:tt )* ) => {
-        web_sys::console::log_1(&format!( $( $t )* ).into());
+        $crate::platform::log(&format!( $( $t )* ));
     }
 }
 
@@ -14,6 +12,8 @@ pub fn set_panic_hook() {
     //
     // For more details see
     // https://github.com/rustwasm/console_error_panic_hook#readme
-    #[cfg(feature = "console_error_panic_hook")]
+    //
+    // The hook logs with `console.error`, so it's only set on wasm32.
+    #[cfg(all(feature = "console_error_panic_hook", target_arch = "wasm32"))]
     console_error_panic_hook::set_once();
 }
@@ -1,12 +1,16 @@
-//! Test suite for the Web and headless browsers.
-
-#![cfg(target_arch = "wasm32")]
+//! Test suite for the Web and headless browsers, which also runs natively
+//! under `cargo test`.
 
 extern crate wasm_bindgen_test;
 extern crate wasm_game_of_life;
 
 use wasm_bindgen_test::*;
-use wasm_game_of_life::Universe;
+use wasm_game_of_life::{
+    decode_apgcode, encode_apgcode, Anchor, ApgcodeError, BoundingBox, Bounds, Format, HashLife,
+    Pattern, PatternError, Random, RenderError, RenderOptions, Resized, Rule, RuleError,
+    RuleFileError, Shape, SnapshotError, SoupError, SparseUniverse, Symmetry, Timer, Timing,
+    Topology, Universe, Viewport, Xoshiro128, MAX_STEP,
+};
 
 wasm_bindgen_test_configure!(run_in_browser);
 
@@ -28,7 +32,7 @@ pub fn expected_spaceship() -> Universe {
     universe
 }
 
-#[wasm_bindgen_test]
+#[wasm_bindgen_test(unsupported = test)]
 pub fn test_tick() {
     // Let's create a smaller Universe with a spaceship
     let mut input_universe = input_spaceship();
@@ -41,3 +45,1681 @@ pub fn test_tick() {
     input_universe.tick();
     assert_eq!(&input_universe.get_cells(), &expected_universe.get_cells());
 }
+
+#[wasm_bindgen_test(unsupported = test)]
+pub fn test_rule_parsing() {
+    let highlife = Rule::parse("B36/S23").unwrap();
+    assert_eq!(highlife.to_string(), "B36/S23");
+    assert_eq!(Rule::parse("23/36").unwrap(), highlife);
+    assert_eq!(Rule::parse("b36s23").unwrap(), highlife);
+    assert_eq!(
+        Rule::parse("B3/S012345678").unwrap().to_string(),
+        "B3/S012345678"
+    );
+
+    assert_eq!(Rule::parse(""), Err(RuleError::Empty));
+    assert_eq!(Rule::parse("B39/S23"), Err(RuleError::InvalidCount('9')));
+    assert!(Rule::parse("B3").is_err());
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+pub fn test_tick_with_rule() {
+    // Under Seeds (B2/S) the two live cells die and give birth to the
+    // cells either side of them.
+    let mut universe = Universe::new();
+    universe.set_width(6);
+    universe.set_height(6);
+    universe.set_rule("B2/S").unwrap();
+    universe.set_cells(&[(2, 2), (2, 3)]);
+    universe.tick();
+
+    let mut expected = Universe::new();
+    expected.set_width(6);
+    expected.set_height(6);
+    expected.set_cells(&[(1, 2), (1, 3), (3, 2), (3, 3)]);
+
+    assert_eq!(&universe.get_cells(), &expected.get_cells());
+    assert!(universe.set_rule("B3/S2x").is_err());
+    assert_eq!(universe.rule(), "B2/S");
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+pub fn test_generations_rule_parsing() {
+    let star_wars = Rule::parse("345/2/4").unwrap();
+    assert_eq!(star_wars, Rule::parse("B2/S345/C4").unwrap());
+    assert_eq!(star_wars.states(), 4);
+    assert_eq!(star_wars.to_string(), "B2/S345/C4");
+    assert_eq!(Rule::parse("/2/3").unwrap().to_string(), "B2/S/C3");
+    assert_eq!(Rule::parse("B3/S23/C2").unwrap(), Rule::conway());
+    assert!(Rule::parse("B2/S/C256").is_err());
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+pub fn test_tick_generations() {
+    // Brian's Brain: live cells always start dying, dying cells then die.
+    let mut universe = Universe::new();
+    universe.set_width(6);
+    universe.set_height(6);
+    universe.clear();
+    universe.set_rule("/2/3").unwrap();
+    universe.set_cells(&[(2, 2), (2, 3)]);
+
+    universe.tick();
+    assert_eq!(universe.get_state(2, 2), 2);
+    assert_eq!(universe.get_state(2, 3), 2);
+    assert_eq!(universe.get_state(1, 2), 1);
+    assert_eq!(universe.get_state(3, 3), 1);
+
+    universe.tick();
+    assert_eq!(universe.get_state(2, 2), 0);
+    assert_eq!(universe.get_state(1, 2), 2);
+    assert!(!universe.get_cells()[2 * 6 + 2]);
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+pub fn test_isotropic_rule_parsing() {
+    let rule = Rule::parse("B2n3/S23-q").unwrap();
+    assert_eq!(rule.to_string(), "B2n3/S23-q");
+    assert!(!rule.is_totalistic());
+    assert!(Rule::conway().is_totalistic());
+
+    assert_eq!(Rule::parse("B3/S2-i34q").unwrap().to_string(), "B3/S2-i34q");
+    assert_eq!(
+        Rule::parse("B2ceaikn/S").unwrap(),
+        Rule::parse("B2/S").unwrap()
+    );
+    assert_eq!(
+        Rule::parse("B2z/S23"),
+        Err(RuleError::InvalidLetter(2, 'z'))
+    );
+    assert_eq!(
+        Rule::parse("B0c/S23"),
+        Err(RuleError::InvalidLetter(0, 'c'))
+    );
+    assert_eq!(
+        Rule::parse("B3-/S23"),
+        Err(RuleError::InvalidLetter(3, '-'))
+    );
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+pub fn test_tick_isotropic() {
+    // Two diagonal cells give two cells an edge-edge (2e) neighbourhood.
+    let mut universe = Universe::new();
+    universe.set_width(6);
+    universe.set_height(6);
+    universe.set_rule("B2e/S").unwrap();
+    universe.set_cells(&[(1, 2), (2, 3)]);
+    universe.tick();
+
+    let mut expected = Universe::new();
+    expected.set_width(6);
+    expected.set_height(6);
+    expected.set_cells(&[(1, 3), (2, 2)]);
+    assert_eq!(&universe.get_cells(), &expected.get_cells());
+
+    universe.set_rule("B2-e/S").unwrap();
+    universe.tick();
+    assert_eq!(universe.get_cells().count_ones(..), 0);
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+pub fn test_larger_than_life_rule_parsing() {
+    let bosco = Rule::parse("R5,C0,M1,S34..58,B34..45,NM").unwrap();
+    assert_eq!(bosco.to_string(), "R5,C0,M1,S34..58,B34..45,NM");
+    assert_eq!(bosco.radius(), 5);
+
+    let ltl = bosco.larger_than_life().unwrap();
+    assert!(ltl.includes_middle());
+    assert_eq!(ltl.shape(), Shape::Moore);
+    assert_eq!(ltl.birth(), 34..=45);
+
+    assert_eq!(Rule::parse("R2,C3,M0,S1..2,B3..3,NN").unwrap().states(), 3);
+    assert!(Rule::parse("R5,C0,M1,S58..34,B34..45,NM").is_err());
+    assert!(Rule::parse("R0,C0,M1,S1..2,B3..3,NM").is_err());
+    assert!(Rule::parse("R5,C0,M1,S34..58,NM").is_err());
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+pub fn test_tick_larger_than_life_conway() {
+    // Radius 1 Larger than Life without the middle cell is plain Life.
+    let mut input_universe = input_spaceship();
+    input_universe.set_rule("R1,C0,M0,S2..3,B3..3,NM").unwrap();
+    input_universe.tick();
+
+    assert_eq!(
+        &input_universe.get_cells(),
+        &expected_spaceship().get_cells()
+    );
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+pub fn test_tick_larger_than_life_shapes() {
+    // A single cell gives birth to every cell that can see it.
+    for (shape, expected) in [("NM", 25), ("NN", 13), ("NC", 21)].iter() {
+        let mut universe = Universe::new();
+        universe.set_width(9);
+        universe.set_height(9);
+        universe
+            .set_rule(&format!("R2,C0,M0,S0..0,B1..1,{}", shape))
+            .unwrap();
+        universe.set_cells(&[(4, 4)]);
+        universe.tick();
+
+        assert_eq!(universe.get_cells().count_ones(..), *expected, "{}", shape);
+    }
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+pub fn test_topology_parsing() {
+    let bounds = Topology::parse(":K64*,32").unwrap();
+    assert_eq!(
+        bounds,
+        Bounds {
+            topology: Topology::KleinBottle,
+            width: Some(64),
+            height: Some(32)
+        }
+    );
+    assert_eq!(
+        Topology::parse("T64,0").unwrap().topology,
+        Topology::HorizontalCylinder
+    );
+    assert_eq!(
+        Topology::parse("T0,32").unwrap().topology,
+        Topology::VerticalCylinder
+    );
+    assert_eq!(Topology::parse("S50").unwrap().width, Some(50));
+    assert_eq!(Topology::parse("P").unwrap().width, None);
+    assert!(Topology::parse("S50,40").is_err());
+    assert!(Topology::parse("P64,0").is_err());
+    assert!(Topology::parse("X").is_err());
+    // A Klein bottle needs its twisted edges marked.
+    assert!(Topology::parse("K64,32").is_err());
+    assert!(Topology::parse("P64*,32").is_err());
+
+    // Nothing maps onto a grid without cells.
+    assert_eq!(Topology::Torus.map(-1, 2, 0, 5), None);
+    assert_eq!(Topology::KleinBottle.map(3, 3, 4, 0), None);
+
+    let mut universe = Universe::new();
+    universe.set_rule("B36/S23:P8,6").unwrap();
+    assert_eq!((universe.width(), universe.height()), (8, 6));
+    assert_eq!(universe.topology(), Topology::Plane);
+    assert_eq!(universe.rule(), "B36/S23:P8,6");
+    assert!(universe.set_rule("B3/S23:Q").is_err());
+
+    // Every cell needs a u32 index.
+    assert!(Universe::fits(65536, 65535));
+    assert!(!Universe::fits(65536, 65536));
+    assert!(universe.set_rule("B3/S23:P65536,65536").is_err());
+    assert_eq!((universe.width(), universe.height()), (8, 6));
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+pub fn test_tick_topologies() {
+    // A single cell under B1/S gives birth to every neighbour, showing where
+    // the neighbours of a cell on the top edge end up.
+    let neighbours_of_top_edge = |topology: Topology| {
+        let mut universe = Universe::new();
+        universe.set_width(5);
+        universe.set_height(5);
+        universe.set_topology(topology);
+        universe.set_rule("B1/S").unwrap();
+        universe.set_cells(&[(0, 1)]);
+        universe.tick();
+        (0..5)
+            .filter(|&col| universe.get_state(4, col) == 1)
+            .collect::<Vec<u32>>()
+    };
+
+    assert_eq!(neighbours_of_top_edge(Topology::Torus), vec![0, 1, 2]);
+    assert_eq!(neighbours_of_top_edge(Topology::Plane), Vec::<u32>::new());
+    assert_eq!(
+        neighbours_of_top_edge(Topology::HorizontalCylinder),
+        Vec::<u32>::new()
+    );
+    assert_eq!(
+        neighbours_of_top_edge(Topology::VerticalCylinder),
+        vec![0, 1, 2]
+    );
+    assert_eq!(neighbours_of_top_edge(Topology::KleinBottle), vec![2, 3, 4]);
+    assert_eq!(
+        neighbours_of_top_edge(Topology::CrossSurface),
+        vec![2, 3, 4]
+    );
+
+    // On a sphere the top edge is joined to the left edge instead.
+    assert_eq!(Topology::Sphere.map(-1, 2, 5, 5), Some((2, 0)));
+    assert_eq!(Topology::Sphere.map(2, -1, 5, 5), Some((0, 2)));
+    assert_eq!(Topology::Sphere.map(5, 1, 5, 5), Some((1, 4)));
+    assert_eq!(Topology::Sphere.map(2, 5, 5, 5), Some((4, 2)));
+    assert_eq!(Topology::Sphere.map(-1, -1, 5, 5), None);
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+pub fn test_stamp_respects_topology() {
+    let mut universe = Universe::new();
+    universe.set_width(6);
+    universe.set_height(6);
+    universe.set_topology(Topology::Plane);
+    universe.glider(0, 0);
+    assert_eq!(universe.get_cells().count_ones(..), 3);
+
+    universe.clear();
+    universe.set_topology(Topology::Torus);
+    universe.glider(0, 0);
+    assert_eq!(universe.get_cells().count_ones(..), 5);
+    assert_eq!(universe.get_state(5, 5), 1);
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+pub fn test_sparse_glider_crosses_tiles() {
+    let mut universe = SparseUniverse::new();
+    universe.set_cells(&[(-10, -9), (-9, -8), (-8, -10), (-8, -9), (-8, -8)]);
+
+    // A glider moves one cell diagonally every four generations.
+    for _ in 0..256 {
+        universe.tick();
+    }
+
+    assert_eq!(universe.generation(), 256);
+    assert_eq!(universe.population(), 5);
+    assert_eq!(
+        universe.bounding_box(),
+        Some(BoundingBox {
+            top: 54,
+            left: 54,
+            bottom: 56,
+            right: 56
+        })
+    );
+    assert!(universe.tile_count() <= 4);
+
+    universe.toggle(54, 55);
+    assert!(!universe.is_alive(54, 55));
+    universe.clear();
+    assert_eq!(universe.bounding_box(), None);
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+pub fn test_sparse_matches_universe() {
+    // An R-pentomino stays well clear of the edges for 100 generations.
+    let r_pentomino = [(127, 128), (127, 129), (128, 127), (128, 128), (129, 128)];
+
+    let mut universe = Universe::new();
+    universe.set_width(256);
+    universe.set_height(256);
+    universe.set_cells(&r_pentomino);
+
+    let mut sparse = SparseUniverse::new();
+    sparse.set_cells(
+        &r_pentomino
+            .iter()
+            .map(|&(row, col)| (row as i64, col as i64))
+            .collect::<Vec<_>>(),
+    );
+
+    for _ in 0..100 {
+        universe.tick();
+        sparse.tick();
+    }
+
+    let mut expected: Vec<(i64, i64)> = (0..256u32)
+        .flat_map(|row| (0..256u32).map(move |col| (row, col)))
+        .filter(|&(row, col)| universe.get_state(row, col) == 1)
+        .map(|(row, col)| (row as i64, col as i64))
+        .collect();
+    let mut actual = sparse.live_cells();
+    expected.sort();
+    actual.sort();
+    assert_eq!(actual, expected);
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+pub fn test_sparse_rejects_unbounded_rules() {
+    let mut universe = SparseUniverse::new();
+    assert_eq!(
+        universe.set_rule("B0/S8"),
+        Err(RuleError::Unsupported("B0/S8".to_string()))
+    );
+    assert!(universe.set_rule("/2/3").is_err());
+    assert!(universe.set_rule("R5,C0,M1,S34..58,B34..45,NM").is_err());
+    assert!(universe.set_rule("B2n3/S23-q").is_ok());
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+pub fn test_hashlife_matches_sparse() {
+    let r_pentomino = [(-1, 0), (-1, 1), (0, -1), (0, 0), (1, 0)];
+
+    let mut hashlife = HashLife::new();
+    hashlife.set_rule("B36/S23").unwrap();
+    hashlife.set_cells(&r_pentomino);
+
+    let mut sparse = SparseUniverse::new();
+    sparse.set_rule("B36/S23").unwrap();
+    sparse.set_cells(&r_pentomino);
+
+    for _ in 0..300 {
+        sparse.tick();
+    }
+    hashlife.advance(300);
+
+    let mut expected = sparse.live_cells();
+    let mut actual = hashlife.live_cells();
+    expected.sort();
+    actual.sort();
+    assert_eq!(hashlife.generation(), 300);
+    assert_eq!(actual, expected);
+    assert_eq!(hashlife.bounding_box(), sparse.bounding_box());
+
+    hashlife.collect_garbage();
+    assert_eq!(hashlife.population(), sparse.population());
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+pub fn test_hashlife_large_steps() {
+    let mut universe = input_spaceship();
+    universe.set_width(8);
+    universe.set_height(8);
+    universe.set_cells(&[(1, 2), (2, 3), (3, 1), (3, 2), (3, 3)]);
+
+    let mut hashlife = HashLife::from_universe(&universe).unwrap();
+    assert_eq!(hashlife.population(), 5);
+
+    // A glider moves one cell diagonally every four generations, so after
+    // 2^40 generations it has travelled 2^38 cells.
+    hashlife.step_pow2(40);
+    let distance = 1 << 38;
+    assert_eq!(hashlife.generation(), 1 << 40);
+    assert_eq!(hashlife.population(), 5);
+    assert_eq!(
+        hashlife.bounding_box(),
+        Some(BoundingBox {
+            top: 1 + distance,
+            left: 1 + distance,
+            bottom: 3 + distance,
+            right: 3 + distance
+        })
+    );
+    assert!(hashlife.to_universe(8, 8).is_err());
+    let oversized = hashlife.to_universe(70000, 70000).err().unwrap();
+    assert_eq!(
+        oversized.to_string(),
+        "a 70000x70000 Universe has too many cells"
+    );
+
+    let mut hashlife = HashLife::from_universe(&universe).unwrap();
+    hashlife.advance(8);
+    let mut expected = universe;
+    for _ in 0..8 {
+        expected.tick();
+    }
+    assert_eq!(
+        hashlife.to_universe(8, 8).unwrap().get_cells(),
+        expected.get_cells()
+    );
+
+    assert_eq!(
+        hashlife.set_rule("B0/S8"),
+        Err(RuleError::Unsupported("B0/S8".to_string()))
+    );
+
+    // Clearing starts counting generations again.
+    hashlife.clear();
+    assert_eq!((hashlife.population(), hashlife.generation()), (0, 0));
+    let mut sparse = SparseUniverse::new();
+    sparse.set_cells(&[(0, 0)]);
+    sparse.tick();
+    sparse.clear();
+    assert_eq!(sparse.generation(), 0);
+
+    // Still lifes can be run for as long as a u64 counts, in steps of at
+    // most 2^MAX_STEP.
+    let mut block = HashLife::new();
+    block.set_cells(&[(0, 0), (0, 1), (1, 0), (1, 1)]);
+    block.advance(u64::MAX);
+    assert_eq!(block.generation(), u64::MAX);
+    assert_eq!(
+        block.bounding_box(),
+        Some(BoundingBox {
+            top: 0,
+            left: 0,
+            bottom: 1,
+            right: 1
+        })
+    );
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+#[should_panic]
+pub fn test_hashlife_step_limit() {
+    HashLife::new().step_pow2(MAX_STEP + 1);
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+#[should_panic]
+pub fn test_too_many_cells() {
+    let mut universe = Universe::empty(70000, 1);
+    universe.set_height(70000);
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+pub fn test_tick_matches_per_cell_reference() {
+    // A width that isn't a multiple of 64 makes rows straddle words.
+    let (width, height) = (70u32, 33u32);
+    let cells: Vec<(u32, u32)> = (0..height)
+        .flat_map(|row| (0..width).map(move |col| (row, col)))
+        .filter(|&(row, col)| (row * 7 + col * 13 + row * col) % 5 < 2)
+        .collect();
+
+    for &(rule, topology) in &[
+        ("B3/S23", Topology::Torus),
+        ("B36/S23", Topology::Plane),
+        ("B2n3/S23-q", Topology::KleinBottle),
+    ] {
+        let mut universe = Universe::empty(width, height);
+        universe.set_rule(rule).unwrap();
+        universe.set_topology(topology);
+        universe.set_cells(&cells);
+        let parsed = Rule::parse(rule).unwrap();
+
+        for _ in 0..4 {
+            let alive = |row: i64, col: i64| {
+                topology
+                    .map(row, col, width, height)
+                    .is_some_and(|(row, col)| universe.get_state(row, col) == 1)
+            };
+            let offsets = [
+                (-1, 0),
+                (-1, 1),
+                (0, 1),
+                (1, 1),
+                (1, 0),
+                (1, -1),
+                (0, -1),
+                (-1, -1),
+            ];
+            let expected: Vec<bool> = (0..height as i64)
+                .flat_map(|row| (0..width as i64).map(move |col| (row, col)))
+                .map(|(row, col)| {
+                    let neighbourhood = offsets
+                        .iter()
+                        .enumerate()
+                        .fold(0u8, |mask, (bit, &(dr, dc))| {
+                            mask | (alive(row + dr, col + dc) as u8) << bit
+                        });
+                    parsed.next_state(alive(row, col), neighbourhood)
+                })
+                .collect();
+
+            universe.tick();
+            let actual: Vec<bool> = (0..(width * height) as usize)
+                .map(|idx| universe.get_cells()[idx])
+                .collect();
+            assert_eq!(actual, expected, "{} on a {}", rule, topology);
+        }
+    }
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+pub fn test_changed_tiles() {
+    let mut universe = Universe::empty(200, 130);
+    let glider = [(1, 2), (2, 3), (3, 1), (3, 2), (3, 3)];
+    let blinker = [(100, 150), (100, 151), (100, 152)];
+    universe.set_cells(&glider);
+    universe.set_cells(&blinker);
+    assert_eq!(universe.tile_size(), 64);
+    assert_eq!(universe.changed_tiles(), (0..12).collect::<Vec<u32>>());
+
+    // With the glider still away from the tile edges only its tile and the
+    // blinker's change.
+    universe.tick();
+    universe.tick();
+    assert_eq!(universe.changed_tiles(), vec![0, 6]);
+
+    universe.toggle(70, 70);
+    assert_eq!(universe.changed_tiles(), vec![0, 5, 6]);
+    universe.toggle(70, 70);
+
+    // The glider crosses into the next tiles and wraps round the torus,
+    // where the last row and column of tiles are only partly filled.
+    let generations = 4 * 130;
+    for _ in 2..generations {
+        universe.tick();
+    }
+
+    let mut expected = Universe::empty(200, 130);
+    expected.set_cells(
+        &glider
+            .iter()
+            .map(|&(row, col)| (row + 130, col + 130))
+            .collect::<Vec<_>>(),
+    );
+    expected.set_cells(&blinker);
+    assert_eq!(universe.get_cells(), expected.get_cells());
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+pub fn test_rle_round_trip() {
+    let rle = "#N Glider\n#O Richard K. Guy\n#C The smallest spaceship.\nx = 6, y = 6, rule = B3/S23\n\n$2bo$3bo$b3o!\n";
+    let universe = Universe::from_rle(rle).unwrap();
+    assert_eq!(universe.get_cells(), input_spaceship().get_cells());
+    assert_eq!(
+        universe.to_rle(),
+        "x = 6, y = 6, rule = B3/S23\n$2bo$3bo$b3o!\n"
+    );
+
+    let mut universe = Universe::empty(6, 6);
+    universe
+        .load_rle_at(5, 5, "x = 3, y = 3\nbo$2bo$3o!")
+        .unwrap();
+    assert_eq!(
+        universe.to_rle(),
+        "x = 6, y = 6, rule = B3/S23\nbo$2o3bo4$o!\n"
+    );
+
+    // Generations states are written as letters, and long rows wrap.
+    let rle = "x = 100, y = 2, rule = B2/S345/C4\n".to_string() + &"AB.".repeat(33) + "C$C!";
+    assert_eq!(
+        Universe::from_rle("x = 1, y = 1, rule = B2/S/C30\npA!")
+            .unwrap()
+            .get_state(0, 0),
+        25
+    );
+    let universe = Universe::from_rle(&rle).unwrap();
+    assert_eq!(universe.get_state(0, 1), 2);
+    assert_eq!(universe.get_state(1, 0), 3);
+    let written = universe.to_rle();
+    assert!(written.lines().all(|line| line.len() <= 70));
+    assert_eq!(Universe::from_rle(&written).unwrap().to_rle(), written);
+
+    let universe = Universe::from_rle("x = 0, y = 0, rule = R2,C0,M1,S2..3,B3..3,NM\n!").unwrap();
+    assert_eq!(universe.rule(), "R2,C0,M1,S2..3,B3..3,NM");
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+pub fn test_rle_errors() {
+    let error = Universe::from_rle("#C comment\nx = 3, y = 3\nbo$2bo$3z!")
+        .err()
+        .unwrap();
+    assert_eq!(
+        error,
+        PatternError::Syntax {
+            line: 3,
+            column: 9,
+            message: "unexpected 'z'".to_string()
+        }
+    );
+    assert_eq!(error.to_string(), "line 3, column 9: unexpected 'z'");
+
+    assert_eq!(
+        Universe::from_rle("x = 3, y = three").err(),
+        Some(PatternError::Syntax {
+            line: 1,
+            column: 8,
+            message: "invalid size 'three'".to_string()
+        })
+    );
+    assert_eq!(
+        Universe::from_rle("x = 3, y = 3, rule = B9/S23\no!").err(),
+        Some(PatternError::Rule {
+            line: 1,
+            column: 15,
+            error: RuleError::InvalidCount('9')
+        })
+    );
+    assert_eq!(
+        Universe::from_rle("x = 3, y = 3\n99999999999o!").err(),
+        Some(PatternError::TooLarge {
+            line: 2,
+            column: 10
+        })
+    );
+    // Runs longer than the header allows are refused before their cells
+    // are made.
+    assert_eq!(
+        Universe::from_rle("x = 3, y = 3\n4000000000o!").err(),
+        Some(PatternError::TooLarge {
+            line: 2,
+            column: 11
+        })
+    );
+    assert_eq!(
+        Universe::from_rle("x = 3, y = 3\n3o$3o$3o$3o!").err(),
+        Some(PatternError::TooLarge {
+            line: 2,
+            column: 11
+        })
+    );
+    assert_eq!(
+        Universe::from_rle("5000000000o!").err(),
+        Some(PatternError::TooLarge {
+            line: 1,
+            column: 10
+        })
+    );
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+pub fn test_pattern_formats() {
+    let glider = Pattern {
+        width: 3,
+        height: 3,
+        cells: vec![(0, 1, 1), (1, 2, 1), (2, 0, 1), (2, 1, 1), (2, 2, 1)],
+        ..Pattern::default()
+    };
+
+    let plaintext = "!Name: Glider\n!The smallest spaceship.\n.O.\n..O\nOOO\n";
+    let life105 = "#Life 1.05\n#D Glider\n#N\n#P -1 -1\n.*\n..*\n***\n";
+    let life106 = "#Life 1.06\n0 -1\n1 0\n-1 1\n0 1\n1 1\n";
+    let rle = "#N Glider\nx = 3, y = 3, rule = B3/S23\nbo$2bo$3o!\n";
+
+    for &(text, format) in &[
+        (plaintext, Format::Plaintext),
+        (life105, Format::Life105),
+        (life106, Format::Life106),
+        (rle, Format::Rle),
+    ] {
+        assert_eq!(Format::detect(text), Some(format));
+        let mut pattern = Pattern::parse(text).unwrap();
+        pattern.cells.sort();
+        assert_eq!(
+            (pattern.width, pattern.height, pattern.cells),
+            (3, 3, glider.cells.clone()),
+            "{:?}",
+            format
+        );
+    }
+
+    let parsed = Pattern::parse(plaintext).unwrap();
+    assert_eq!(parsed.name.as_deref(), Some("Glider"));
+    assert_eq!(parsed.comments, vec!["The smallest spaceship."]);
+    assert_eq!(parsed.write(Format::Plaintext).unwrap(), plaintext);
+    assert_eq!(
+        Pattern::parse(life105).unwrap().rule.as_deref(),
+        Some("B3/S23")
+    );
+    assert_eq!(
+        glider.write(Format::Life106).unwrap(),
+        "#Life 1.06\n1 0\n2 1\n0 2\n1 2\n2 2\n"
+    );
+    assert_eq!(
+        glider.write(Format::Life105).unwrap(),
+        "#Life 1.05\n#N\n#P -1 -1\n.*\n..*\n***\n"
+    );
+
+    // Multiple blocks and a rule in Life 1.05.
+    let blocks = Pattern::parse("#Life 1.05\n#R 23/36\n#P 10 10\n**\n#P -10 -10\n*\n").unwrap();
+    assert_eq!(blocks.rule.as_deref(), Some("23/36"));
+    assert_eq!((blocks.width, blocks.height), (22, 21));
+
+    let universe = Universe::from_pattern(life106).unwrap();
+    assert_eq!(
+        universe.to_rle(),
+        "x = 3, y = 3, rule = B3/S23\nbo$2bo$3o!\n"
+    );
+    assert_eq!(
+        Universe::from_pattern(&universe.to_plaintext())
+            .unwrap()
+            .to_rle(),
+        universe.to_rle()
+    );
+    assert_eq!(
+        Universe::from_pattern(&universe.to_life105())
+            .unwrap()
+            .to_rle(),
+        universe.to_rle()
+    );
+
+    let mut universe = Universe::empty(6, 6);
+    universe.load_pattern_at(1, 1, plaintext).unwrap();
+    assert_eq!(universe.get_cells(), input_spaceship().get_cells());
+
+    // Patterns built by hand may have any rule.
+    assert_eq!(
+        glider.to_universe().unwrap().to_rle(),
+        "x = 3, y = 3, rule = B3/S23\nbo$2bo$3o!\n"
+    );
+    let invalid = Pattern {
+        rule: Some("B3/S23:Q8".to_string()),
+        ..glider.clone()
+    };
+    assert_eq!(
+        invalid.to_universe().err(),
+        Some(PatternError::Rule {
+            line: 1,
+            column: 1,
+            error: RuleError::InvalidTopology("Q8".to_string())
+        })
+    );
+    let oversized = Pattern {
+        width: 70000,
+        height: 70000,
+        ..glider.clone()
+    };
+    assert_eq!(
+        oversized.to_universe().err(),
+        Some(PatternError::TooLarge { line: 1, column: 1 })
+    );
+
+    assert_eq!(
+        Pattern::parse(".O.\n.X.").err(),
+        Some(PatternError::Syntax {
+            line: 2,
+            column: 2,
+            message: "unexpected 'X'".to_string()
+        })
+    );
+    assert_eq!(
+        Pattern::parse("#Life 1.06\n1 2\n3 four\n").err(),
+        Some(PatternError::Syntax {
+            line: 3,
+            column: 3,
+            message: "invalid coordinate 'four'".to_string()
+        })
+    );
+    assert!(Pattern::parse("{}").is_err());
+
+    // Coordinates at the ends of an i64 are too large rather than
+    // overflowing.
+    let too_large = |line, column| Some(PatternError::TooLarge { line, column });
+    assert_eq!(
+        Pattern::parse("#Life 1.05\n#P 9223372036854775806 0\n.**\n").err(),
+        too_large(3, 3)
+    );
+    assert_eq!(
+        Pattern::parse("#Life 1.05\n#P 0 9223372036854775807\n*\n").err(),
+        too_large(3, 1)
+    );
+    assert_eq!(
+        Pattern::parse("#Life 1.06\n9223372036854775807 0\n").err(),
+        too_large(2, 1)
+    );
+    assert_eq!(
+        Pattern::parse("#Life 1.06\n0 -9223372036854775808\n0 0\n").err(),
+        too_large(3, 1)
+    );
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+pub fn test_macrocell() {
+    let macrocell = "[M2] (golly 2.0)\n#R B3/S23\n$$..*$...*$.***$\n4 1 0 0 0\n";
+
+    let universe = Universe::from_pattern(macrocell).unwrap();
+    assert_eq!(
+        universe.to_rle(),
+        "x = 3, y = 3, rule = B3/S23\nbo$2bo$3o!\n"
+    );
+    assert_eq!(
+        Universe::from_pattern(&universe.to_macrocell().unwrap())
+            .unwrap()
+            .to_rle(),
+        universe.to_rle()
+    );
+
+    // Bounded topologies survive a round trip, and rules HashLife can't
+    // run aren't written.
+    let mut bounded = Universe::empty(12, 10);
+    bounded.set_rule("B36/S23:P12,10").unwrap();
+    bounded.glider(2, 3);
+    let text = bounded.to_macrocell().unwrap();
+    assert!(text.contains("#R B36/S23:P12,10\n"));
+    let reread = Universe::from_pattern(&text).unwrap();
+    assert_eq!(reread.rule(), "B36/S23:P12,10");
+    // Macrocell files don't record where the cells were, so they move to
+    // the top left corner.
+    assert_eq!(
+        reread.to_rle(),
+        "x = 12, y = 10, rule = B36/S23:P12,10\no$b2o$2o!\n"
+    );
+    assert_eq!(HashLife::from_macrocell(&text).unwrap().rule(), "B36/S23");
+    bounded.set_rule("B2/S/C3").unwrap();
+    assert_eq!(
+        bounded.to_macrocell().err(),
+        Some(RuleError::Unsupported("B2/S/C3".to_string()))
+    );
+
+    let mut hashlife = HashLife::from_macrocell(macrocell).unwrap();
+    assert_eq!(hashlife.population(), 5);
+    hashlife.advance(4);
+    let reread = HashLife::from_macrocell(&hashlife.to_macrocell()).unwrap();
+    assert_eq!(reread.live_cells(), hashlife.live_cells());
+    assert_eq!(
+        Pattern::parse(&hashlife.to_macrocell())
+            .unwrap()
+            .write(Format::Rle)
+            .unwrap(),
+        universe.to_rle()
+    );
+
+    assert_eq!(
+        HashLife::from_macrocell("[M2]\n4 2 0 0 0\n").err(),
+        Some(PatternError::Syntax {
+            line: 2,
+            column: 3,
+            message: "node 2 is not defined yet".to_string()
+        })
+    );
+    assert_eq!(
+        HashLife::from_macrocell("[M2]\n*$\n5 1 0 0 0\n").err(),
+        Some(PatternError::Syntax {
+            line: 3,
+            column: 3,
+            message: "node 1 is not at level 4".to_string()
+        })
+    );
+    assert_eq!(
+        HashLife::from_macrocell("[M2]\n2 0 0 0 0\n").err(),
+        Some(PatternError::Syntax {
+            line: 2,
+            column: 1,
+            message: "unsupported level 2".to_string()
+        })
+    );
+
+    // Every node reuses the one below it twice, giving 2^37 cells spread
+    // too far apart to flatten into a Universe.
+    let mut huge = "[M2]\n*$\n".to_string();
+    for level in 4..=40 {
+        huge += &format!("{} {} 0 0 {}\n", level, level - 3, level - 3);
+    }
+    let hashlife = HashLife::from_macrocell(&huge).unwrap();
+    assert_eq!(hashlife.population(), 1 << 37);
+    assert_eq!(
+        Pattern::parse(&huge).err(),
+        Some(PatternError::TooLarge {
+            line: 39,
+            column: 1
+        })
+    );
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+pub fn test_apgcode() {
+    assert_eq!(
+        encode_apgcode(&[(0, 0), (0, 1), (1, 0), (1, 1)], "B3/S23").unwrap(),
+        "xs4_33"
+    );
+    assert_eq!(
+        encode_apgcode(&[(5, 4), (5, 5), (5, 6)], "B3/S23").unwrap(),
+        "xp2_7"
+    );
+    assert_eq!(
+        encode_apgcode(&[(0, 1), (1, 2), (2, 0), (2, 1), (2, 2)], "B3/S23").unwrap(),
+        "xq4_153"
+    );
+
+    let pentadecathlon = [
+        (0, 2),
+        (0, 7),
+        (1, 0),
+        (1, 1),
+        (1, 3),
+        (1, 4),
+        (1, 5),
+        (1, 6),
+        (1, 8),
+        (1, 9),
+        (2, 2),
+        (2, 7),
+    ];
+    assert_eq!(
+        encode_apgcode(&pentadecathlon, "B3/S23").unwrap(),
+        "xp15_4r4z4r4"
+    );
+
+    let glider = decode_apgcode("xq4_153").unwrap();
+    assert_eq!(encode_apgcode(&glider, "B3/S23").unwrap(), "xq4_153");
+    assert_eq!(decode_apgcode("xs2_0y01z1").unwrap(), vec![(0, 5), (5, 0)]);
+
+    // A block straddling the corner of a torus is still one object.
+    let mut universe = Universe::empty(64, 64);
+    universe.stamp_apgcode(63, 63, "xs4_33").unwrap();
+    assert_eq!(universe.apgcode_at(0, 0).unwrap(), "xs4_33");
+    universe.glider(20, 20);
+    assert_eq!(universe.apgcode_at(20, 21).unwrap(), "xq4_153");
+
+    assert_eq!(universe.apgcode_at(40, 40), Err(ApgcodeError::Empty));
+    assert_eq!(
+        encode_apgcode(&[(0, 1), (0, 2), (1, 0), (1, 1), (2, 1)], "B3/S23"),
+        Err(ApgcodeError::NotPeriodic)
+    );
+    assert!(matches!(
+        encode_apgcode(&glider, "23/3/3"),
+        Err(ApgcodeError::Rule(_))
+    ));
+    assert_eq!(
+        decode_apgcode("xs4"),
+        Err(ApgcodeError::Invalid {
+            column: 4,
+            message: "expected a '_'".to_string()
+        })
+    );
+    assert_eq!(
+        decode_apgcode("xs4_3#"),
+        Err(ApgcodeError::Invalid {
+            column: 6,
+            message: "unexpected '#'".to_string()
+        })
+    );
+    assert!(decode_apgcode("yl144_1").is_err());
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+pub fn test_render() {
+    let mut universe = Universe::empty(5, 5);
+    universe.set_cells(&[(2, 1), (2, 2), (2, 3)]);
+
+    let png = universe.to_png(&RenderOptions::new()).unwrap();
+    let mut decoder = png::Decoder::new(&png[..]);
+    decoder.set_transformations(png::Transformations::EXPAND);
+    let mut reader = decoder.read_info().unwrap();
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels).unwrap();
+    assert_eq!((info.width, info.height), (31, 31));
+
+    let pixel = |x: usize, y: usize| &pixels[(y * 31 + x) * 3..][..3];
+    assert_eq!(pixel(0, 0), [0xCC, 0xCC, 0xCC]);
+    assert_eq!(pixel(2 * 6 + 3, 2 * 6 + 3), [0, 0, 0]);
+    assert_eq!(pixel(2 * 6 + 3, 6 + 3), [0xFF, 0xFF, 0xFF]);
+
+    let options = RenderOptions {
+        cell_size: 1,
+        grid: false,
+        ..RenderOptions::new()
+    };
+    let gif = universe.record_gif(&options, 3, 10).unwrap();
+    let mut decoder = gif::DecodeOptions::new().read_info(&gif[..]).unwrap();
+    let mut frames = Vec::new();
+    while let Some(frame) = decoder.read_next_frame().unwrap() {
+        assert_eq!((frame.width, frame.height, frame.delay), (5, 5, 10));
+        frames.push(frame.buffer.to_vec());
+    }
+    assert_eq!(frames.len(), 3);
+    assert_ne!(frames[0], frames[1]);
+    assert_eq!(frames[0], frames[2]);
+    assert_eq!(frames[1][5 + 2], 1);
+    assert_eq!(
+        universe.get_cells().ones().collect::<Vec<_>>(),
+        vec![11, 12, 13]
+    );
+
+    assert_eq!(
+        Universe::empty(20000, 1).to_png(&RenderOptions::new()),
+        Err(RenderError::Size {
+            width: 120001,
+            height: 7
+        })
+    );
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+pub fn test_svg() {
+    let mut universe = Universe::empty(5, 5);
+    universe.set_cells(&[(2, 1), (2, 2), (2, 3), (0, 4)]);
+
+    let svg = universe.to_svg(&RenderOptions::new());
+    assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"31\" height=\"31\""));
+    assert!(svg.contains("<rect x=\"7\" y=\"13\" width=\"17\" height=\"5\"/>"));
+    assert!(svg.contains("<rect x=\"25\" y=\"1\" width=\"5\" height=\"5\"/>"));
+    assert_eq!(svg.matches("<rect x=").count(), 2);
+    assert!(svg.contains("M0.5 0V31M6.5 0V31"));
+
+    let mut options = RenderOptions {
+        cell_size: 2,
+        grid: false,
+        ..RenderOptions::new()
+    };
+    options.set_crop(2, 2, 3, 3);
+    assert_eq!(
+        universe.to_svg(&options),
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"6\" height=\"6\" viewBox=\"0 0 6 6\" shape-rendering=\"crispEdges\">\n\
+         <rect width=\"6\" height=\"6\" fill=\"#ffffff\"/>\n\
+         <g fill=\"#000000\">\n<rect x=\"0\" y=\"0\" width=\"4\" height=\"2\"/>\n</g>\n\
+         </svg>\n"
+    );
+
+    // Crops are cut down to the Universe, and apply to the other images too.
+    options.set_crop(3, 3, 10, 10);
+    assert!(universe
+        .to_svg(&options)
+        .contains("width=\"4\" height=\"4\""));
+    options.set_crop(5, 0, 1, 1);
+    assert_eq!(
+        universe.to_png(&options),
+        Err(RenderError::Size {
+            width: 2,
+            height: 0
+        })
+    );
+}
+
+#[derive(Default)]
+struct Recorder(std::rc::Rc<std::cell::RefCell<Vec<String>>>);
+
+impl Timing for Recorder {
+    fn start(&mut self, label: &str) {
+        self.0.borrow_mut().push(format!("start {}", label));
+    }
+
+    fn end(&mut self, label: &str) {
+        self.0.borrow_mut().push(format!("end {}", label));
+    }
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+pub fn test_platform() {
+    let recorder = Recorder::default();
+    let events = recorder.0.clone();
+    {
+        let _timer = Timer::with_timing("work", recorder);
+        assert_eq!(*events.borrow(), vec!["start work"]);
+    }
+    assert_eq!(*events.borrow(), vec!["start work", "end work"]);
+
+    // Random soups and timed ticks work on every platform.
+    let mut universe = Universe::new();
+    assert!(universe.get_cells().count_ones(..) > 0);
+    universe.tick();
+    universe.reset();
+    assert_eq!(universe.generation(), 0);
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen_test]
+pub fn test_browser_random() {
+    let mut random = wasm_game_of_life::BrowserRandom;
+    let (mut any, mut all) = (0, u32::MAX);
+    for _ in 0..256 {
+        let value = random.next_u32();
+        any |= value;
+        all &= value;
+    }
+    // Every bit, including the top ones, is sometimes set and sometimes not.
+    assert_eq!((any, all), (u32::MAX, 0));
+
+    let alive = (0..4096).filter(|_| random.chance(0.25)).count();
+    assert!((850..1200).contains(&alive), "{} of 4096", alive);
+
+    let mut universe = Universe::empty(64, 64);
+    universe.set_density(0.25);
+    universe.reset();
+    let alive = universe.get_cells().count_ones(..);
+    assert!((850..1200).contains(&alive), "{} cells are alive", alive);
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+pub fn test_seeded_soup() {
+    let first: Vec<u32> = (0..3)
+        .scan(Xoshiro128::new(1), |random, _| Some(random.next_u32()))
+        .collect();
+    assert_eq!(first, vec![2146930148, 2585199205, 3670091704]);
+
+    let universe = Universe::with_seed(64, 64, 42);
+    assert_eq!(
+        universe.get_cells(),
+        Universe::with_seed(64, 64, 42).get_cells()
+    );
+    assert_ne!(
+        universe.get_cells(),
+        Universe::with_seed(64, 64, 43).get_cells()
+    );
+
+    let mut other = Universe::new();
+    other.tick();
+    other.set_width(64);
+    other.set_height(64);
+    other.reset_with_seed(42);
+    assert_eq!(other.get_cells(), universe.get_cells());
+    assert_eq!(other.generation(), 0);
+
+    other.set_density(0.0);
+    other.reset_with_seed(42);
+    assert_eq!(other.get_cells().count_ones(..), 0);
+    other.set_density(2.0);
+    assert_eq!(other.density(), 1.0);
+    other.reset_with_seed(42);
+    assert_eq!(other.get_cells().count_ones(..), 64 * 64);
+    other.set_density(0.25);
+    other.reset_with_seed(42);
+    let alive = other.get_cells().count_ones(..);
+    assert!((900..1150).contains(&alive), "{} cells are alive", alive);
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+pub fn test_seed_odd_sizes() {
+    for &(width, height) in [(100, 100), (1, 7), (7, 1), (33, 3), (5, 5)].iter() {
+        let cells = (width * height) as usize;
+        // Over many seeds every cell is alive at least once, including
+        // those in the last, partly used block.
+        let mut seen = vec![false; cells];
+        for seed in 0..64 {
+            let mut universe = Universe::with_seed(width, height, seed);
+            for _ in 0..3 {
+                let bits = universe.get_cells();
+                let blocks: usize = bits
+                    .as_slice()
+                    .iter()
+                    .map(|block| block.count_ones() as usize)
+                    .sum();
+                assert_eq!(
+                    blocks,
+                    bits.count_ones(..),
+                    "{}x{} seed {}",
+                    width,
+                    height,
+                    seed
+                );
+                assert_eq!(bits.as_slice().len(), cells.div_ceil(32));
+                universe.tick();
+            }
+
+            universe.reset_with_seed(seed);
+            for idx in universe.get_cells().ones() {
+                seen[idx] = true;
+            }
+        }
+        assert!(seen.iter().all(|&alive| alive), "{}x{}", width, height);
+
+        let mut universe = Universe::with_seed(width, height, 1);
+        universe.set_width(width + 1);
+        universe.reset();
+        assert_eq!(universe.get_cells().len(), ((width + 1) * height) as usize);
+        universe.set_height(height + 2);
+        assert_eq!(
+            universe.get_cells().len(),
+            ((width + 1) * (height + 2)) as usize
+        );
+        assert_eq!(universe.get_cells().count_ones(..), 0);
+    }
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+pub fn test_resize() {
+    let glider = [(0, 1), (1, 2), (2, 0), (2, 1), (2, 2)];
+    let live = |universe: &Universe| -> Vec<(u32, u32)> {
+        universe
+            .get_cells()
+            .ones()
+            .map(|idx| (idx as u32 / universe.width(), idx as u32 % universe.width()))
+            .collect()
+    };
+
+    let mut universe = Universe::empty(5, 4);
+    universe.set_cells(&glider);
+    universe.tick();
+    let before = live(&universe);
+    let generation = universe.generation();
+
+    let resized = universe.resize(9, 8, Anchor::TopLeft);
+    assert_eq!(
+        resized,
+        Resized {
+            row_offset: 0,
+            col_offset: 0,
+            cropped: 0
+        }
+    );
+    assert_eq!((universe.width(), universe.height()), (9, 8));
+    assert_eq!(live(&universe), before);
+    assert_eq!(universe.generation(), generation);
+
+    // Centring grows evenly, with the odd row at the bottom, and shrinking
+    // back undoes it.
+    let resized = universe.resize(12, 11, Anchor::Centre);
+    assert_eq!(
+        resized,
+        Resized {
+            row_offset: 1,
+            col_offset: 1,
+            cropped: 0
+        }
+    );
+    let moved: Vec<(u32, u32)> = before
+        .iter()
+        .map(|&(row, col)| (row + 1, col + 1))
+        .collect();
+    assert_eq!(live(&universe), moved);
+    assert_eq!(universe.resize(9, 8, Anchor::Centre).row_offset, -1);
+    assert_eq!(live(&universe), before);
+
+    let resized = universe.resize(10, 10, Anchor::BottomRight);
+    assert_eq!(
+        resized,
+        Resized {
+            row_offset: 2,
+            col_offset: 1,
+            cropped: 0
+        }
+    );
+
+    // Shrinking towards the top left crops everything below and right.
+    let resized = universe.resize(3, 4, Anchor::BottomRight);
+    assert_eq!((resized.row_offset, resized.col_offset), (-6, -7));
+    assert_eq!(
+        resized.cropped as usize + live(&universe).len(),
+        before.len()
+    );
+    assert!(resized.cropped > 0);
+    assert_eq!(universe.get_cells().len(), 12);
+
+    // Offsets can be larger than an i32.
+    let widest = Anchor::BottomRight.offset((1, 1), (u32::MAX, 1));
+    assert_eq!(widest, (0, u32::MAX as i64 - 1));
+
+    // Every state is carried over, and the board keeps evolving.
+    let mut universe = Universe::empty(6, 6);
+    universe.set_rule("B2/S/C3").unwrap();
+    universe.set_cells(&[(2, 2), (2, 3)]);
+    universe.tick();
+    let states: Vec<u8> = (0..6).map(|col| universe.get_state(2, col)).collect();
+    universe.resize(8, 8, Anchor::Right);
+    let resized: Vec<u8> = (2..8).map(|col| universe.get_state(3, col)).collect();
+    assert_eq!(resized, states);
+    universe.tick();
+    assert!(universe.get_cells().count_ones(..) > 0);
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+pub fn test_symmetric_soup() {
+    let codes = [
+        ("C1", (16, 16)),
+        ("C2_1", (15, 15)),
+        ("C2_2", (15, 16)),
+        ("C2_4", (16, 16)),
+        ("C4_1", (15, 15)),
+        ("C4_4", (16, 16)),
+        ("D2_+1", (16, 15)),
+        ("D2_+2", (16, 16)),
+        ("D2_x", (16, 16)),
+        ("D4_+1", (15, 15)),
+        ("D4_+2", (15, 16)),
+        ("D4_+4", (16, 16)),
+        ("D4_x1", (15, 15)),
+        ("D4_x4", (16, 16)),
+        ("D8_1", (15, 15)),
+        ("D8_4", (16, 16)),
+    ];
+
+    for &(code, size) in codes.iter() {
+        let symmetry = Symmetry::parse(code).unwrap();
+        assert_eq!(symmetry.code(), code);
+        assert_eq!(symmetry.fit(16, 16), size);
+
+        let mut universe = Universe::empty(24, 24);
+        universe.set_topology(Topology::Plane);
+        let placed = universe.symmetric_soup(2, 3, 16, 16, symmetry, 7);
+        let (width, height) = size;
+        assert_eq!(
+            placed,
+            Viewport {
+                row: 2,
+                col: 3,
+                width,
+                height
+            }
+        );
+        let state = |row: u32, col: u32| universe.get_state(2 + row, 3 + col);
+        for row in 0..height {
+            for col in 0..width {
+                let (flipped_row, flipped_col) = (height - 1 - row, width - 1 - col);
+                let images: Vec<(u32, u32)> = match code {
+                    "C1" => vec![],
+                    "C2_1" | "C2_2" | "C2_4" => vec![(flipped_row, flipped_col)],
+                    "C4_1" | "C4_4" => vec![(col, flipped_row), (flipped_row, flipped_col)],
+                    "D2_+1" | "D2_+2" => vec![(flipped_row, col)],
+                    "D2_x" => vec![(col, row)],
+                    "D4_+1" | "D4_+2" | "D4_+4" => vec![(flipped_row, col), (row, flipped_col)],
+                    "D4_x1" | "D4_x4" => vec![(col, row), (flipped_col, flipped_row)],
+                    _ => vec![(col, flipped_row), (row, flipped_col), (col, row)],
+                };
+                for (image_row, image_col) in images {
+                    assert_eq!(
+                        state(row, col),
+                        state(image_row, image_col),
+                        "{} at {},{}",
+                        code,
+                        row,
+                        col
+                    );
+                }
+            }
+        }
+
+        // Only the soup's rectangle is filled, and the same seed gives the
+        // same soup.
+        let alive = universe.get_cells().count_ones(..);
+        assert!(alive > 0);
+        let mut again = Universe::empty(24, 24);
+        again.symmetric_soup(2, 3, 16, 16, symmetry, 7);
+        assert_eq!(again.get_cells(), universe.get_cells());
+        let outside = (0..24)
+            .flat_map(|row| (0..24).map(move |col| (row, col)))
+            .filter(|&(row, col)| row < 2 || col < 3 || row >= 2 + height || col >= 3 + width)
+            .filter(|&(row, col)| universe.get_state(row, col) != 0)
+            .count();
+        assert_eq!(outside, 0);
+    }
+
+    assert_eq!(Symmetry::parse("D4_+"), Ok(Symmetry::D4Plus1));
+    assert_eq!(Symmetry::parse("D4_x").unwrap().to_string(), "D4_x1");
+    assert_eq!(
+        Symmetry::parse("C3"),
+        Err(SoupError::UnknownSymmetry("C3".to_string()))
+    );
+    assert_eq!(Symmetry::D8_4.fit(20, 11), (10, 10));
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+pub fn test_snapshot() {
+    let mut universe = Universe::empty(100, 70);
+    universe.set_rule("B36/S23:P100,70").unwrap();
+    universe.glider(10, 10);
+    universe.tick();
+    universe.tick();
+
+    let bytes = universe.serialize();
+    assert!(bytes.len() < 100);
+    let restored = Universe::deserialize(&bytes).unwrap();
+    assert_eq!((restored.width(), restored.height()), (100, 70));
+    assert_eq!(restored.rule(), "B36/S23:P100,70");
+    assert_eq!(restored.topology(), Topology::Plane);
+    assert_eq!(restored.generation(), 2);
+    assert_eq!(restored.get_cells(), universe.get_cells());
+
+    // Dying cells of Generations rules are kept.
+    let mut universe = Universe::empty(8, 8);
+    universe.set_rule("B2/S/C4").unwrap();
+    universe.set_cells(&[(3, 3), (3, 4)]);
+    universe.tick();
+    let restored = Universe::deserialize(&universe.serialize()).unwrap();
+    assert_eq!(restored.rule(), universe.rule());
+    for (row, col) in (0..8).flat_map(|row| (0..8).map(move |col| (row, col))) {
+        assert_eq!(restored.get_state(row, col), universe.get_state(row, col));
+    }
+
+    let mut damaged = bytes.clone();
+    damaged[20] ^= 1;
+    assert_eq!(
+        Universe::deserialize(&damaged).err(),
+        Some(SnapshotError::Checksum)
+    );
+    assert_eq!(
+        Universe::deserialize(&bytes[..3]).err(),
+        Some(SnapshotError::Truncated)
+    );
+    assert_eq!(
+        Universe::deserialize(b"x = 3, y = 3").err(),
+        Some(SnapshotError::NotASnapshot)
+    );
+    let mut newer = bytes.clone();
+    newer[4] = 2;
+    assert_eq!(
+        Universe::deserialize(&newer).err(),
+        Some(SnapshotError::UnsupportedVersion(2))
+    );
+
+    // A header claiming more cells than a u32 can index is refused before
+    // anything is allocated.
+    let mut huge = bytes[..bytes.len() - 4].to_vec();
+    huge[5..9].copy_from_slice(&100_000u32.to_le_bytes());
+    huge[9..13].copy_from_slice(&100_000u32.to_le_bytes());
+    huge.extend(crc32fast::hash(&huge).to_le_bytes());
+    assert!(matches!(
+        Universe::deserialize(&huge),
+        Err(SnapshotError::Corrupt(_))
+    ));
+
+    // Cells are checked as well as the checksum, since anyone can write one.
+    let version = bytes[4];
+    let snapshot = |size: u32, rule: &str, cells: &[u8]| {
+        let cells = miniz_oxide::deflate::compress_to_vec(cells, 6);
+        let mut bytes = b"LIFE".to_vec();
+        bytes.push(version);
+        bytes.extend(size.to_le_bytes());
+        bytes.extend(size.to_le_bytes());
+        bytes.extend(0u64.to_le_bytes());
+        bytes.push(0);
+        bytes.extend((rule.len() as u32).to_le_bytes());
+        bytes.extend(rule.as_bytes());
+        bytes.extend((cells.len() as u32).to_le_bytes());
+        bytes.extend(cells);
+        bytes.extend(crc32fast::hash(&bytes).to_le_bytes());
+        bytes
+    };
+    assert!(Universe::deserialize(&snapshot(2, "B2/S/C4", &[0, 1, 3, 0])).is_ok());
+    let out_of_range = snapshot(2, "B2/S/C4", &[0, 1, 255, 0]);
+    assert!(matches!(
+        Universe::deserialize(&out_of_range),
+        Err(SnapshotError::Corrupt(_))
+    ));
+    let restored = Universe::deserialize(&snapshot(5, "B3/S23", &[0xff; 4])).unwrap();
+    assert_eq!(restored.get_cells().as_slice(), &[(1 << 25) - 1]);
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+pub fn test_share_string() {
+    let mut universe = Universe::empty(64, 48);
+    universe.set_rule("B3/S23:K64*,48").unwrap();
+    universe.pulsar(20, 20);
+
+    let shared = universe.to_share_string();
+    assert!(shared
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+    assert!(shared.len() < 150);
+
+    let restored = Universe::from_share_string(&shared).unwrap();
+    assert_eq!(restored.rule(), "B3/S23:K64*,48");
+    assert_eq!(restored.get_cells(), universe.get_cells());
+
+    assert!(matches!(
+        Universe::from_share_string("not a board!").err(),
+        Some(SnapshotError::Encoding(_))
+    ));
+    assert_eq!(
+        Universe::from_share_string(&shared[..shared.len() - 4]).err(),
+        Some(SnapshotError::Checksum)
+    );
+}
+
+const WIREWORLD: &str = "\
+@RULE WireWorld
+# 0 is empty, 1 an electron head, 2 an electron tail and 3 a wire.
+@TABLE
+n_states:4
+neighborhood:Moore
+symmetries:permute
+var a={0,1,2,3}
+var b={0,1,2,3}
+var c={0,1,2,3}
+var d={0,1,2,3}
+var e={0,1,2,3}
+var f={0,1,2,3}
+var g={0,1,2,3}
+var h={0,1,2,3}
+var i={0,2,3}
+var j={0,2,3}
+var k={0,2,3}
+var l={0,2,3}
+var m={0,2,3}
+var n={0,2,3}
+var o={0,2,3}
+1,a,b,c,d,e,f,g,h,2
+2,a,b,c,d,e,f,g,h,3
+3,1,i,j,k,l,m,n,o,1
+3,1,1,j,k,l,m,n,o,1
+@COLORS
+1 255 255 0
+";
+
+fn row_states(universe: &Universe, row: u32) -> Vec<u8> {
+    (0..universe.width())
+        .map(|col| universe.get_state(row, col))
+        .collect()
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+pub fn test_rule_file_table() {
+    let mut universe = Universe::empty(8, 3);
+    universe.set_topology(Topology::Plane);
+    universe.load_rule_file(WIREWORLD).unwrap();
+    assert_eq!(universe.rule(), "WireWorld:P8,3");
+    assert_eq!(universe.state_count(), 4);
+
+    universe.load_pattern_at(1, 1, "BACCCC!").unwrap();
+    universe.tick();
+    assert_eq!(row_states(&universe, 1), vec![0, 3, 2, 1, 3, 3, 3, 0]);
+    universe.tick();
+    assert_eq!(row_states(&universe, 1), vec![0, 3, 3, 2, 1, 3, 3, 0]);
+    assert!(universe.get_cells()[8 + 4]);
+
+    // Loaded rule files survive a snapshot.
+    let restored = Universe::deserialize(&universe.serialize()).unwrap();
+    assert_eq!(restored.rule(), "WireWorld:P8,3");
+    assert_eq!(row_states(&restored, 1), row_states(&universe, 1));
+
+    // With rotate4 symmetry one transition covers all four directions.
+    let spread = "@RULE Spread\n@TABLE\nn_states:2\nneighborhood:vonNeumann\nsymmetries:rotate4\n0,1,0,0,0,1\n";
+    let mut universe = Universe::empty(5, 5);
+    universe.load_rule_file(spread).unwrap();
+    universe.set_cells(&[(2, 2)]);
+    universe.tick();
+    assert_eq!(
+        universe.get_cells().ones().collect::<Vec<_>>(),
+        vec![7, 11, 12, 13, 17]
+    );
+
+    universe.set_rule("B3/S23").unwrap();
+    assert_eq!(universe.state_count(), 2);
+    assert_eq!(universe.rule(), "B3/S23");
+
+    let error = |text: &str| Universe::empty(4, 4).load_rule_file(text).err();
+    assert_eq!(
+        error("@TABLE\n"),
+        Some(RuleFileError {
+            line: 1,
+            message: "expected '@RULE'".to_string()
+        })
+    );
+    assert_eq!(
+        error("@RULE Bad\n@TABLE\nn_states:2\n0,1,0,0,0,1\n"),
+        Some(RuleFileError {
+            line: 4,
+            message: "expected 10 entries, found 6".to_string()
+        })
+    );
+    assert_eq!(
+        error("@RULE Bad\n@TABLE\nn_states:2\nneighborhood:vonNeumann\n0,1,0,0,2,1\n"),
+        Some(RuleFileError {
+            line: 5,
+            message: "state 2 is out of range".to_string()
+        })
+    );
+    assert!(error("@RULE Bad\n@TABLE\nn_states:2\nneighborhood:hexagonal\n").is_some());
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+pub fn test_rule_file_tree() {
+    // Every cell takes the state of its northern neighbour, branching on
+    // north, west, east, south and then the cell itself.
+    let fall = "\
+@RULE Fall
+@TREE
+num_states=2
+num_neighbors=4
+num_nodes=9
+1 0 0
+1 1 1
+2 0 0
+2 1 1
+3 2 2
+3 3 3
+4 4 4
+4 5 5
+5 6 7
+";
+    let mut universe = Universe::empty(5, 5);
+    universe.load_rule_file(fall).unwrap();
+    universe.set_cells(&[(1, 2), (1, 3)]);
+    universe.tick();
+    assert_eq!(
+        universe.get_cells().ones().collect::<Vec<_>>(),
+        vec![12, 13]
+    );
+    for _ in 0..3 {
+        universe.tick();
+    }
+    assert_eq!(universe.get_cells().ones().collect::<Vec<_>>(), vec![2, 3]);
+
+    assert_eq!(
+        Universe::empty(4, 4)
+            .load_rule_file(&fall.replace("5 6 7", "5 6 8"))
+            .err(),
+        Some(RuleFileError {
+            line: 14,
+            message: "branch 8 is not valid at level 5".to_string()
+        })
+    );
+}
+
+#[cfg(feature = "serde")]
+#[wasm_bindgen_test(unsupported = test)]
+pub fn test_serde() {
+    let mut universe = Universe::empty(20, 10);
+    universe.set_rule("B36/S23:T20,0").unwrap();
+    universe.glider(4, 4);
+    universe.tick();
+
+    let json = serde_json::to_string(&universe).unwrap();
+    assert_eq!(
+        json,
+        r#"{"width":20,"height":10,"rule":"B36/S23","topology":"HorizontalCylinder","generation":1,"cells":"3$4bo$5bo$3b3o!\n"}"#
+    );
+    let restored: Universe = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored.rule(), universe.rule());
+    assert_eq!(restored.generation(), 1);
+    assert_eq!(restored.get_cells(), universe.get_cells());
+    let huge = json.replace(
+        r#""width":20,"height":10"#,
+        r#""width":100000,"height":100000"#,
+    );
+    assert!(serde_json::from_str::<Universe>(&huge).is_err());
+
+    let rule: Rule = serde_json::from_str(r#""B3/S23""#).unwrap();
+    assert_eq!(serde_json::to_string(&rule).unwrap(), r#""B3/S23""#);
+    assert!(serde_json::from_str::<Rule>(r#""B9/S""#).is_err());
+
+    let pattern = Pattern::parse("x = 3, y = 1\n3o!").unwrap();
+    let json = serde_json::to_string(&pattern).unwrap();
+    assert_eq!(serde_json::from_str::<Pattern>(&json).unwrap(), pattern);
+    let unruled = r#"{"name":null,"author":null,"comments":[],"width":1,"height":1,"cells":[]}"#;
+    assert_eq!(serde_json::from_str::<Pattern>(unruled).unwrap().rule, None);
+    let invalid = json.replace(r#""rule":null"#, r#""rule":"B3/S23:Q8""#);
+    assert_ne!(invalid, json);
+    assert!(serde_json::from_str::<Pattern>(
+        &json.replace(r#""rule":null"#, r#""rule":"B3/S23:P3,1""#)
+    )
+    .is_ok());
+    assert!(serde_json::from_str::<Pattern>(&invalid).is_err());
+    let oversized = json.replace(r#""width":3,"height":1"#, r#""width":70000,"height":70000"#);
+    assert_ne!(oversized, json);
+    assert!(serde_json::from_str::<Pattern>(&oversized).is_err());
+
+    // A `.rule` file is kept whole by a Universe but only named by a
+    // Pattern.
+    let mut wireworld = Universe::empty(4, 4);
+    wireworld.load_rule_file(WIREWORLD).unwrap();
+    wireworld.load_pattern_at(1, 1, "C!").unwrap();
+    let restored: Universe =
+        serde_json::from_str(&serde_json::to_string(&wireworld).unwrap()).unwrap();
+    assert_eq!(restored.get_state(1, 1), 3);
+    let named = serde_json::to_string(&Pattern::from_universe(&wireworld)).unwrap();
+    assert!(serde_json::from_str::<Pattern>(&named).is_err());
+    assert!(Universe::from_rle(&wireworld.to_rle()).is_err());
+
+    let too_small =
+        r#"{"width":2,"height":2,"rule":"B3/S23","topology":"Torus","generation":0,"cells":"3o!"}"#;
+    assert!(serde_json::from_str::<Universe>(too_small).is_err());
+}
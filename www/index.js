@@ -6,22 +6,53 @@ const GRID_COLOR = "#CCCCCC";
 const DEAD_COLOR = "#FFFFFF";
 const ALIVE_COLOR = "#000000";
 
-const universe = Universe.new();
-const width = universe.width();
-const height = universe.height();
+// A board shared in the link's fragment is opened in place of a random one.
+const openUniverse = () => {
+  const shared = window.location.hash.slice(1);
+  if (shared) {
+    try {
+      return Universe.from_share_string(shared);
+    } catch (error) {
+      console.warn(`Couldn't open the shared board: ${error}`);
+    }
+  }
+  return Universe.new();
+};
 
+const universe = openUniverse();
 const canvas = document.getElementById("game-of-life-canvas");
-canvas.height = (CELL_SIZE + 1) * height + 1;
-canvas.width = (CELL_SIZE + 1) * width + 1;
-
 const ctx = canvas.getContext('2d');
 
+// Only the tiles that changed since the last frame are redrawn.
+const tileSize = universe.tile_size();
+
+// The size of the Universe, which a rule with a topology suffix such as
+// `B3/S23:P32,32` can change.
+let width;
+let height;
+let tileColumns;
+let allTiles;
+
+const fitCanvas = () => {
+  width = universe.width();
+  height = universe.height();
+  tileColumns = Math.ceil(width / tileSize);
+  allTiles = [...Array(tileColumns * Math.ceil(height / tileSize)).keys()];
+
+  // Resizing the canvas clears it, so the grid is drawn again.
+  canvas.height = (CELL_SIZE + 1) * height + 1;
+  canvas.width = (CELL_SIZE + 1) * width + 1;
+  drawGrid();
+};
+
 let animationId = null;
 
 const frameLengthInput = document.getElementById("frame-length");
 const playPauseButton = document.getElementById("play-pause");
 const randomizeButton = document.getElementById("randomize");
 const extinguishButton = document.getElementById("extinguish");
+const ruleInput = document.getElementById("rule");
+const shareButton = document.getElementById("share");
 
 let frameLength = 1;
 
@@ -39,10 +70,45 @@ playPauseButton.addEventListener("click", event => {
 
 randomizeButton.addEventListener("click", event => {
   universe.reset();
+  drawCells();
 });
 
 extinguishButton.addEventListener("click", event => {
   universe.clear();
+  drawCells();
+});
+
+ruleInput.value = universe.rule();
+ruleInput.addEventListener("change", event => {
+  try {
+    universe.set_rule(event.target.value);
+    ruleInput.setCustomValidity("");
+    if (universe.width() !== width || universe.height() !== height) {
+      fitCanvas();
+    }
+  } catch (error) {
+    ruleInput.setCustomValidity(error);
+  }
+  ruleInput.reportValidity();
+  drawCells();
+});
+
+// Keep the link pointing at the board as it stands, without adding to the
+// browser history.
+const updateLink = () => {
+  history.replaceState(null, "", `#${universe.to_share_string()}`);
+};
+
+shareButton.addEventListener("click", event => {
+  updateLink();
+  if (navigator.clipboard) {
+    navigator.clipboard.writeText(window.location.href);
+  }
+});
+
+// Pasting another shared link into the address bar only changes the hash.
+window.addEventListener("hashchange", event => {
+  window.location.reload();
 });
 
 const play = () => {
@@ -54,19 +120,21 @@ const pause = () => {
   playPauseButton.textContent = "▶";
   cancelAnimationFrame(animationId);
   animationId = null;
+  updateLink();
 }
 
 const renderLoop = () => {
   fps.render();
 
   let i = 0;
+  const dirtyTiles = new Set();
 
   for(i=0; i < frameLength; i++) {
     universe.tick();
+    universe.changed_tiles().forEach(tile => dirtyTiles.add(tile));
   }
 
-  drawGrid();
-  drawCells();
+  drawCells(dirtyTiles);
 
   animationId = requestAnimationFrame(renderLoop);
 };
@@ -98,51 +166,96 @@ const getIndex = (row, col) => {
   return row * width + col;
 };
 
+// Visit every cell within the given tiles.
+const forEachCell = (tiles, visit) => {
+  for (const tile of tiles) {
+    const top = Math.floor(tile / tileColumns) * tileSize;
+    const left = (tile % tileColumns) * tileSize;
+
+    for (let row = top; row < Math.min(top + tileSize, height); row++) {
+      for (let col = left; col < Math.min(left + tileSize, width); col++) {
+        visit(row, col);
+      }
+    }
+  }
+};
+
 const bitIsSet = (pos, arr) => {
   const byte = Math.floor(pos / 8);
   const mask = 1 << (pos % 8);
   return (arr[byte] & mask) === mask;
 };
 
-const drawCells = () => {
+// Dying cells of Generations rules fade from the alive colour towards the
+// dead colour as they age.
+const stateColor = (state, stateCount) => {
+  if (state === 0) {
+    return DEAD_COLOR;
+  }
+
+  const shade = Math.round(255 * (state - 1) / (stateCount - 1));
+  return `rgb(${shade}, ${shade}, ${shade})`;
+};
+
+const drawStates = (tiles) => {
+  const stateCount = universe.state_count();
+  const statesPtr = universe.states();
+  const states = new Uint8Array(memory.buffer, statesPtr, width * height);
+
+  ctx.beginPath();
+
+  forEachCell(tiles, (row, col) => {
+    ctx.fillStyle = stateColor(states[getIndex(row, col)], stateCount);
+    ctx.fillRect(
+      col * (CELL_SIZE + 1) + 1,
+      row * (CELL_SIZE + 1) + 1,
+      CELL_SIZE,
+      CELL_SIZE
+    );
+  });
+
+  ctx.stroke();
+};
+
+const drawCells = (tiles = allTiles) => {
+  if (universe.state_count() > 2) {
+    return drawStates(tiles);
+  }
+
   const cellsPtr = universe.cells();
-  const cells = new Uint8Array(memory.buffer, cellsPtr, width * height / 8);
+  const cells = new Uint8Array(memory.buffer, cellsPtr, Math.ceil(width * height / 8));
 
   ctx.beginPath();
 
   ctx.fillStyle = ALIVE_COLOR;
-  for (let row = 0; row < height; row ++) {
-    for (let col = 0; col < width; col ++) {
-      const idx = getIndex(row, col);
-      if (!bitIsSet(idx, cells)) {
-        continue;
-      }
-
-      ctx.fillRect(
-        col * (CELL_SIZE + 1) + 1,
-        row * (CELL_SIZE + 1) + 1,
-        CELL_SIZE,
-        CELL_SIZE
-      );
+  forEachCell(tiles, (row, col) => {
+    const idx = getIndex(row, col);
+    if (!bitIsSet(idx, cells)) {
+      return;
     }
-  }
 
-  ctx.fillStyle = DEAD_COLOR;
-  for (let row = 0; row < height; row++) {
-    for (let col = 0; col < width; col++) {
-      const idx = getIndex(row, col);
-      if (bitIsSet(idx, cells)) {
-        continue;
-      }
+    ctx.fillRect(
+      col * (CELL_SIZE + 1) + 1,
+      row * (CELL_SIZE + 1) + 1,
+      CELL_SIZE,
+      CELL_SIZE
+    );
+  });
 
-      ctx.fillRect(
-        col * (CELL_SIZE + 1) + 1,
-        row * (CELL_SIZE + 1) + 1,
-        CELL_SIZE,
-        CELL_SIZE
-      );
+  ctx.fillStyle = DEAD_COLOR;
+  forEachCell(tiles, (row, col) => {
+    const idx = getIndex(row, col);
+    if (bitIsSet(idx, cells)) {
+      return;
     }
-  }
+
+    ctx.fillRect(
+      col * (CELL_SIZE + 1) + 1,
+      row * (CELL_SIZE + 1) + 1,
+      CELL_SIZE,
+      CELL_SIZE
+    );
+  });
 
   ctx.stroke();
 }
@@ -167,8 +280,7 @@ canvas.addEventListener("click", event => {
     universe.toggle(row, col);
   }
 
-  drawGrid();
-  drawCells();
+  drawCells(universe.changed_tiles());
 })
 
 const fps = new class {
@@ -214,6 +326,6 @@ max of last 100 = ${Math.round(max)}
   }
 };
 
-drawGrid();
+fitCanvas();
 drawCells();
 play();